@@ -0,0 +1,305 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use crate::{
+    chunk::Chunk,
+    opcode::Opcode,
+    value::{Obj, Value},
+};
+
+// Reads the `.loxasm` text format back into a `Chunk`. The format is the disassembler listing,
+// so anything printed by `Dissasembler::disassemble` assembles back to the same chunk:
+//
+//   ; Comments run from ';' to the end of the line.
+//   == main ==                 Sets the chunk name, "main" if left out.
+//   .constant 0 "hello"        Adds a constant to the pool. Indices must be given in order.
+//                              Strings escape \n, \r, \t, \\, \' and \" with a backslash.
+//   .line 3                    Following instructions are on source line 3.
+//   start:                     Defines a label at the current offset.
+//   0000    1 Constant 0 '1'   An instruction, as printed by the disassembler.
+//
+// The offset and line columns of an instruction are optional. With both columns present the
+// offset is checked against where the instruction actually lands, and the line column is either
+// a line number or '|' for "same line as before". With only one column it is read as the line.
+//
+// A constant operand may be followed by its value in single quotes. If the index hasn't been
+// defined by a `.constant` directive yet, that value becomes the constant.
+pub struct Assembler {
+    chunk: Chunk,
+    labels: HashMap<String, usize>,
+    line: usize,
+}
+
+impl Assembler {
+    pub fn assemble(source: &str) -> Result<Chunk, AssembleError> {
+        let mut assembler = Assembler {
+            chunk: Chunk::new("main".to_string()),
+            labels: HashMap::new(),
+            line: 1,
+        };
+
+        for (index, text) in source.lines().enumerate() {
+            let comment = text
+                .match_indices(';')
+                .map(|(position, _)| position)
+                .find(|position| !Self::in_quotes(text, *position));
+            let text = match comment {
+                Some(comment) => &text[..comment],
+                None => text,
+            };
+
+            assembler
+                .assemble_line(text.trim())
+                .map_err(|message| AssembleError {
+                    line: index + 1,
+                    message,
+                })?;
+        }
+
+        Ok(assembler.chunk)
+    }
+
+    fn in_quotes(text: &str, position: usize) -> bool {
+        let mut quoted = false;
+        let mut escaped = false;
+        for c in text[..position].chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                _ => {}
+            }
+        }
+        quoted
+    }
+
+    fn assemble_line(&mut self, text: &str) -> Result<(), String> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(name) = text.strip_prefix("==").and_then(|t| t.strip_suffix("==")) {
+            self.chunk.name = name.trim().to_string();
+            return Ok(());
+        }
+
+        if let Some(directive) = text.strip_prefix('.') {
+            return self.assemble_directive(directive);
+        }
+
+        if let Some(label) = text.strip_suffix(':') {
+            let offset = self.chunk.code.len();
+            if self.labels.insert(label.to_string(), offset).is_some() {
+                return Err(format!("Label '{}' is already defined", label));
+            }
+            return Ok(());
+        }
+
+        self.assemble_instruction(text)
+    }
+
+    fn assemble_directive(&mut self, directive: &str) -> Result<(), String> {
+        let (name, rest) = split_word(directive);
+
+        match name {
+            "constant" => {
+                let (index, literal) = split_word(rest);
+                let index = parse_index(index)?;
+                if index != self.chunk.constants.len() {
+                    return Err(format!(
+                        "Expected constant {} next, found {}",
+                        self.chunk.constants.len(),
+                        index
+                    ));
+                }
+                let value = parse_literal(literal)?;
+                self.chunk.add_constant(value);
+            }
+            "line" => {
+                self.line = parse_index(rest)?;
+            }
+            _ => return Err(format!("Unknown directive '.{}'", name)),
+        }
+
+        Ok(())
+    }
+
+    fn assemble_instruction(&mut self, text: &str) -> Result<(), String> {
+        let mut columns = Vec::new();
+        let mut rest = text;
+        loop {
+            let (word, remainder) = split_word(rest);
+            if word == "|" || word.parse::<usize>().is_ok() {
+                columns.push(word);
+                rest = remainder;
+            } else {
+                break;
+            }
+        }
+
+        match columns.as_slice() {
+            [] => {}
+            [line] => self.line = parse_index(line)?,
+            [offset, line] => {
+                let offset = parse_index(offset)?;
+                if offset != self.chunk.code.len() {
+                    return Err(format!(
+                        "Instruction is at offset {:04}, not {:04}",
+                        self.chunk.code.len(),
+                        offset
+                    ));
+                }
+                if *line != "|" {
+                    self.line = parse_index(line)?;
+                }
+            }
+            _ => return Err("Too many columns before instruction".to_string()),
+        }
+
+        let (mnemonic, operands) = split_word(rest);
+        let opcode = match opcode_for_mnemonic(mnemonic) {
+            Some(opcode) => opcode,
+            None => return Err(format!("Unknown instruction '{}'", mnemonic)),
+        };
+
+        match opcode {
+            Opcode::Constant | Opcode::ConstantLong | Opcode::AddConstant => {
+                let (index, value) = split_word(operands);
+                let index = parse_index(index)?;
+                self.define_inline_constant(index, value)?;
+
+                if opcode.operand_len() == 1 {
+                    if index > u8::MAX as usize {
+                        return Err(format!("Constant {} does not fit in a byte", index));
+                    }
+                    self.chunk.write(opcode, self.line);
+                    self.chunk.write([index as u8], self.line);
+                } else {
+                    self.chunk.write(opcode, self.line);
+                    self.chunk.write((index as u32).to_be_bytes(), self.line);
+                }
+            }
+            _ => {
+                if !operands.is_empty() {
+                    return Err(format!("{:?} does not take operands", opcode));
+                }
+                self.chunk.write(opcode, self.line);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn define_inline_constant(&mut self, index: usize, value: &str) -> Result<(), String> {
+        if index < self.chunk.constants.len() {
+            return Ok(());
+        }
+
+        if index > self.chunk.constants.len() || value.is_empty() {
+            return Err(format!("Constant {} is not defined", index));
+        }
+
+        let literal = value
+            .strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .unwrap_or(value);
+        let value = parse_literal(literal)?;
+        self.chunk.add_constant(value);
+
+        Ok(())
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim()),
+        None => (text, ""),
+    }
+}
+
+fn parse_index(text: &str) -> Result<usize, String> {
+    text.trim()
+        .parse::<usize>()
+        .map_err(|_| format!("Expected a number, found '{}'", text.trim()))
+}
+
+fn parse_literal(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        return unescape(&text[1..text.len() - 1]).map(|s| Value::obj(Obj::String(s)));
+    }
+
+    match text {
+        "true" => Ok(Value::bool(true)),
+        "false" => Ok(Value::bool(false)),
+        "nil" => Ok(Value::nil()),
+        _ => match text.parse::<f64>() {
+            Ok(n) => Ok(Value::number(n)),
+            Err(_) => Err(format!("Invalid constant '{}'", text)),
+        },
+    }
+}
+
+// Undoes the escapes `Dissasembler::format_constant` writes.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('\\' | '\'' | '"')) => unescaped.push(c),
+            Some(c) => return Err(format!("Invalid escape '\\{}' in constant", c)),
+            None => return Err("Unfinished escape at the end of constant".to_string()),
+        }
+    }
+    Ok(unescaped)
+}
+
+fn opcode_for_mnemonic(mnemonic: &str) -> Option<Opcode> {
+    let opcode = match mnemonic {
+        "Return" => Opcode::Return,
+        "Constant" => Opcode::Constant,
+        "ConstantLong" => Opcode::ConstantLong,
+        "Negate" => Opcode::Negate,
+        "Add" => Opcode::Add,
+        "Subtract" => Opcode::Subtract,
+        "Divide" => Opcode::Divide,
+        "Multiply" => Opcode::Multiply,
+        "Nil" => Opcode::Nil,
+        "True" => Opcode::True,
+        "False" => Opcode::False,
+        "Not" => Opcode::Not,
+        "Equal" => Opcode::Equal,
+        "Greater" => Opcode::Greater,
+        "Less" => Opcode::Less,
+        "NotEqual" => Opcode::NotEqual,
+        "GreaterEqual" => Opcode::GreaterEqual,
+        "LessEqual" => Opcode::LessEqual,
+        "AddConstant" => Opcode::AddConstant,
+        "ReturnNil" => Opcode::ReturnNil,
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+#[derive(Debug)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
@@ -0,0 +1,281 @@
+use crate::{
+    compiler::{get_rule, Diagnostic, Parser, Precedence},
+    scanner::{CompilerError, Scanner, Token, TokenType},
+    vm::InterpretError,
+};
+
+/// Where a node came from. Offsets count bytes, as token offsets do, and the line is the
+/// one the node ends on, which is the line the compiler records for its instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub length: usize,
+    pub line: usize,
+}
+
+impl Span {
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            start: self.start,
+            length: end.start + end.length - self.start,
+            line: end.line,
+        }
+    }
+
+    pub fn lexeme<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.start + self.length]
+    }
+}
+
+impl From<Token> for Span {
+    fn from(token: Token) -> Self {
+        Span {
+            start: token.start,
+            length: token.length,
+            line: token.line,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    String(String),
+    True,
+    False,
+    Nil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+impl BinaryOperator {
+    fn from_token_type(token_type: TokenType) -> BinaryOperator {
+        match token_type {
+            TokenType::Plus => BinaryOperator::Add,
+            TokenType::Minus => BinaryOperator::Subtract,
+            TokenType::Star => BinaryOperator::Multiply,
+            TokenType::Slash => BinaryOperator::Divide,
+            TokenType::EqualEqual => BinaryOperator::Equal,
+            TokenType::BangEqual => BinaryOperator::NotEqual,
+            TokenType::Greater => BinaryOperator::Greater,
+            TokenType::GreaterEqual => BinaryOperator::GreaterEqual,
+            TokenType::Less => BinaryOperator::Less,
+            TokenType::LessEqual => BinaryOperator::LessEqual,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn token_type(&self) -> TokenType {
+        match self {
+            BinaryOperator::Add => TokenType::Plus,
+            BinaryOperator::Subtract => TokenType::Minus,
+            BinaryOperator::Multiply => TokenType::Star,
+            BinaryOperator::Divide => TokenType::Slash,
+            BinaryOperator::Equal => TokenType::EqualEqual,
+            BinaryOperator::NotEqual => TokenType::BangEqual,
+            BinaryOperator::Greater => TokenType::Greater,
+            BinaryOperator::GreaterEqual => TokenType::GreaterEqual,
+            BinaryOperator::Less => TokenType::Less,
+            BinaryOperator::LessEqual => TokenType::LessEqual,
+        }
+    }
+
+    pub fn precedence(&self) -> Precedence {
+        get_rule(self.token_type()).precedence
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == Precedence::Equality || self.precedence() == Precedence::Comparison
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Literal),
+    // The span of a grouping covers both of its parentheses.
+    Grouping(Box<Expr>),
+    Unary {
+        operator: UnaryOperator,
+        operator_span: Span,
+        operand: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: BinaryOperator,
+        operator_span: Span,
+        right: Box<Expr>,
+    },
+    // Stands in for an expression that failed to parse, so that parsing can carry on and report
+    // the same errors as the single-pass compiler. A script that parsed never contains one.
+    Invalid,
+}
+
+/// A whole script, which for now is a single expression. `end` is the span of the `Eof` token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub body: Expr,
+    pub end: Span,
+}
+
+/// Parses source into a `Script`, reporting errors exactly as `Compiler` does.
+pub struct AstParser<'src> {
+    parser: Parser<'src>,
+    scanner: Scanner<'src>,
+}
+
+impl<'src> AstParser<'src> {
+    pub fn new(source: &'src str) -> Self {
+        AstParser {
+            parser: Parser::new(source),
+            scanner: Scanner::new(source),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Script, InterpretError> {
+        self.parser.advance(&mut self.scanner);
+
+        let body = self.expression();
+
+        self.parser.consume(
+            &mut self.scanner,
+            TokenType::Eof,
+            "Expect end of expression.",
+        );
+
+        match self.parser.had_error {
+            true => Err(InterpretError::CompileError),
+            false => Ok(Script {
+                body,
+                end: self.parser.previous.into(),
+            }),
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.parser.diagnostics
+    }
+
+    fn expression(&mut self) -> Expr {
+        self.parse_precedence(Precedence::Assignment)
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) -> Expr {
+        self.parser.advance(&mut self.scanner);
+        let token = self.parser.previous;
+
+        let mut left = match token.token_type {
+            TokenType::LeftParen => self.grouping(token),
+            TokenType::Minus | TokenType::Bang => self.unary(token),
+            TokenType::Number => {
+                let value = token.lexeme(self.parser.source).parse::<f64>().unwrap();
+                Self::literal(Literal::Number(value), token)
+            }
+            TokenType::String => {
+                let value = token.lexeme(self.parser.source);
+                let value = value[1..value.len() - 1].to_string();
+                Self::literal(Literal::String(value), token)
+            }
+            TokenType::True => Self::literal(Literal::True, token),
+            TokenType::False => Self::literal(Literal::False, token),
+            TokenType::Nil => Self::literal(Literal::Nil, token),
+            _ => {
+                self.parser.error_at_current(&CompilerError {
+                    message: "Expect expression.".to_string(),
+                    line: token.line,
+                });
+                Expr {
+                    kind: ExprKind::Invalid,
+                    span: token.into(),
+                }
+            }
+        };
+
+        while precedence <= get_rule(self.parser.current.token_type).precedence {
+            self.parser.advance(&mut self.scanner);
+            left = self.binary(left);
+        }
+
+        left
+    }
+
+    fn literal(literal: Literal, token: Token) -> Expr {
+        Expr {
+            kind: ExprKind::Literal(literal),
+            span: token.into(),
+        }
+    }
+
+    fn grouping(&mut self, open: Token) -> Expr {
+        let inner = self.expression();
+        self.parser.consume(
+            &mut self.scanner,
+            TokenType::RightParen,
+            "Expect ')' after expression.",
+        );
+
+        Expr {
+            kind: ExprKind::Grouping(Box::new(inner)),
+            span: Span::from(open).to(self.parser.previous.into()),
+        }
+    }
+
+    fn unary(&mut self, token: Token) -> Expr {
+        let operator = match token.token_type {
+            TokenType::Bang => UnaryOperator::Not,
+            TokenType::Minus => UnaryOperator::Negate,
+            _ => unreachable!(),
+        };
+
+        let operand = self.parse_precedence(Precedence::Unary);
+
+        Expr {
+            span: Span::from(token).to(operand.span),
+            kind: ExprKind::Unary {
+                operator,
+                operator_span: token.into(),
+                operand: Box::new(operand),
+            },
+        }
+    }
+
+    fn binary(&mut self, left: Expr) -> Expr {
+        let token = self.parser.previous;
+        let operator = BinaryOperator::from_token_type(token.token_type);
+
+        let right = self.parse_precedence(operator.precedence().next());
+
+        Expr {
+            span: left.span.to(right.span),
+            kind: ExprKind::Binary {
+                left: Box::new(left),
+                operator,
+                operator_span: token.into(),
+                right: Box::new(right),
+            },
+        }
+    }
+}
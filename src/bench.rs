@@ -0,0 +1,128 @@
+use std::{collections::HashMap, error::Error, fmt::Display, time::Instant};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    chunk::Chunk,
+    vm::{ExecutionState, InterpretError, Vm},
+};
+
+/// The directory of benchmark programs that is run when no other is given.
+pub const DEFAULT_DIR: &str = "bench";
+
+pub struct BenchResult {
+    pub name: String,
+    pub runs: usize,
+    pub instructions: usize,
+    // Seconds per run.
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl BenchResult {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.mean
+    }
+}
+
+#[derive(Debug)]
+pub enum BenchError {
+    InvalidBaseline(String),
+}
+
+impl Display for BenchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BenchError::InvalidBaseline(message) => write!(f, "Invalid baseline: {}", message),
+        }
+    }
+}
+
+impl Error for BenchError {}
+
+pub struct Bench {}
+
+impl Bench {
+    /// Runs a chunk `runs` times on a fresh VM each time and times `Vm::run`, leaving out
+    /// loading and verifying the chunk. Program output is thrown away.
+    ///
+    /// NOTE: The instruction count comes from stepping through the chunk once beforehand.
+    ///       There is no control flow yet, so every run executes the same instructions.
+    pub fn run(name: &str, chunk: &Chunk, runs: usize) -> Result<BenchResult, InterpretError> {
+        let mut vm = Self::new_vm(chunk)?;
+        let mut instructions = 0;
+        loop {
+            instructions += 1;
+            if vm.step()? == ExecutionState::Finished {
+                break;
+            }
+        }
+
+        let mut times = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let mut vm = Self::new_vm(chunk)?;
+
+            let start = Instant::now();
+            vm.run()?;
+            times.push(start.elapsed().as_secs_f64());
+        }
+
+        let mean = times.iter().sum::<f64>() / runs as f64;
+        let variance = times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / runs as f64;
+
+        Ok(BenchResult {
+            name: name.to_string(),
+            runs,
+            instructions,
+            mean,
+            stddev: variance.sqrt(),
+        })
+    }
+
+    pub fn to_json(results: &[BenchResult]) -> Json {
+        let benchmarks: Vec<Json> = results
+            .iter()
+            .map(|result| {
+                json!({
+                    "name": result.name,
+                    "runs": result.runs,
+                    "instructions": result.instructions,
+                    "mean": result.mean,
+                    "stddev": result.stddev,
+                    "instructionsPerSecond": result.instructions_per_second(),
+                })
+            })
+            .collect();
+
+        json!({ "benchmarks": benchmarks })
+    }
+
+    /// Reads the mean time of each benchmark from JSON written by `to_json`.
+    pub fn parse_baseline(contents: &str) -> Result<HashMap<String, f64>, BenchError> {
+        let error = |message: &str| BenchError::InvalidBaseline(message.to_string());
+
+        let json: Json = serde_json::from_str(contents).map_err(|e| error(&e.to_string()))?;
+        let benchmarks = json["benchmarks"]
+            .as_array()
+            .ok_or_else(|| error("Expected a 'benchmarks' array"))?;
+
+        let mut baseline = HashMap::new();
+        for benchmark in benchmarks {
+            let name = benchmark["name"]
+                .as_str()
+                .ok_or_else(|| error("Expected every benchmark to have a 'name'"))?;
+            let mean = benchmark["mean"]
+                .as_f64()
+                .ok_or_else(|| error("Expected every benchmark to have a 'mean'"))?;
+            baseline.insert(name.to_string(), mean);
+        }
+
+        Ok(baseline)
+    }
+
+    fn new_vm(chunk: &Chunk) -> Result<Vm, InterpretError> {
+        let mut vm = Vm::builder().output(Box::new(std::io::sink())).build();
+        vm.load(chunk.clone())?;
+        Ok(vm)
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{dissasembler::Dissasembler, opcode::Opcode, value::Value};
 use std::fmt::Debug;
 
+#[derive(Clone)]
 pub struct Chunk {
     pub code: Vec<u8>,
     pub constants: Vec<Value>,
@@ -19,8 +20,9 @@ impl Chunk {
     }
 
     pub fn write<O: Into<Vec<u8>>>(&mut self, value: O, line: usize) {
+        let offset = self.code.len();
         self.code.extend_from_slice(&value.into());
-        self.add_line(line);
+        self.add_line(line, offset);
     }
 
     // NOTE: In the book it asks to support 24bit constants, but why not 32bit :^)
@@ -34,15 +36,17 @@ impl Chunk {
         }
     }
 
-    fn add_line(&mut self, line: usize) {
+    // NOTE: A line starts at the first of the bytes written for it, which matters when a whole
+    //       instruction is written at once.
+    fn add_line(&mut self, line: usize, offset: usize) {
         if self.lines.is_empty() {
-            self.lines.push((line, self.code.len() - 1));
+            self.lines.push((line, offset));
         } else {
             let last_line = self.lines.last().unwrap().0;
             if last_line == line {
                 return;
             }
-            self.lines.push((line, self.code.len() - 1));
+            self.lines.push((line, offset));
         }
     }
 
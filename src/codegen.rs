@@ -0,0 +1,115 @@
+use crate::{
+    ast::{BinaryOperator, Expr, ExprKind, Literal, Script, UnaryOperator},
+    chunk::Chunk,
+    opcode::Opcode,
+    value::{Obj, Value},
+};
+
+/// Generates a chunk from a parsed script. Without superinstructions the output is byte for byte
+/// what `Compiler` produces for the same source, line table included.
+pub struct CodeGenerator {
+    chunk: Chunk,
+    superinstructions: bool,
+}
+
+impl CodeGenerator {
+    pub fn generate(script: &Script, superinstructions: bool) -> Chunk {
+        let mut generator = CodeGenerator {
+            chunk: Chunk::new("main".to_string()),
+            superinstructions,
+        };
+
+        match &script.body.kind {
+            ExprKind::Literal(Literal::Nil) if superinstructions => {
+                generator.chunk.write(Opcode::ReturnNil, script.end.line);
+            }
+            _ => {
+                generator.expression(&script.body);
+                generator.chunk.write(Opcode::Return, script.end.line);
+            }
+        }
+
+        generator.chunk
+    }
+
+    // NOTE: Every instruction is given the line its node ends on, as the single-pass compiler
+    //       emits each one after parsing the last token of its operands.
+    fn expression(&mut self, expr: &Expr) {
+        let line = expr.span.line;
+
+        match &expr.kind {
+            ExprKind::Literal(literal) => match literal {
+                Literal::Number(value) => self.constant(Value::number(*value), line),
+                Literal::String(value) => {
+                    self.constant(Value::obj(Obj::String(value.clone())), line)
+                }
+                Literal::True => self.chunk.write(Opcode::True, line),
+                Literal::False => self.chunk.write(Opcode::False, line),
+                Literal::Nil => self.chunk.write(Opcode::Nil, line),
+            },
+            ExprKind::Grouping(inner) => self.expression(inner),
+            ExprKind::Unary {
+                operator, operand, ..
+            } => {
+                self.expression(operand);
+                match operator {
+                    UnaryOperator::Negate => self.chunk.write(Opcode::Negate, line),
+                    UnaryOperator::Not => self.chunk.write(Opcode::Not, line),
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.expression(left);
+                if *operator == BinaryOperator::Add && self.add_constant(right, line) {
+                    return;
+                }
+
+                self.expression(right);
+                match operator {
+                    BinaryOperator::Add => self.chunk.write(Opcode::Add, line),
+                    BinaryOperator::Subtract => self.chunk.write(Opcode::Subtract, line),
+                    BinaryOperator::Multiply => self.chunk.write(Opcode::Multiply, line),
+                    BinaryOperator::Divide => self.chunk.write(Opcode::Divide, line),
+                    BinaryOperator::Equal => self.chunk.write(Opcode::Equal, line),
+                    BinaryOperator::NotEqual => self.pair(Opcode::Equal, Opcode::Not, line),
+                    BinaryOperator::Greater => self.chunk.write(Opcode::Greater, line),
+                    BinaryOperator::GreaterEqual => self.pair(Opcode::Less, Opcode::Not, line),
+                    BinaryOperator::Less => self.chunk.write(Opcode::Less, line),
+                    BinaryOperator::LessEqual => self.pair(Opcode::Greater, Opcode::Not, line),
+                }
+            }
+            ExprKind::Invalid => unreachable!(),
+        }
+    }
+
+    // Emits `AddConstant` when the right operand is a literal whose constant fits in its byte.
+    fn add_constant(&mut self, right: &Expr, line: usize) -> bool {
+        let value = match &right.kind {
+            ExprKind::Literal(Literal::Number(value)) => Value::number(*value),
+            ExprKind::Literal(Literal::String(value)) => Value::obj(Obj::String(value.clone())),
+            _ => return false,
+        };
+        if !self.superinstructions || self.chunk.constants.len() > u8::MAX as usize {
+            return false;
+        }
+
+        let constant = self.chunk.add_constant(value);
+        self.chunk.write(Opcode::AddConstant, line);
+        self.chunk.write([constant as u8], line);
+        true
+    }
+
+    fn constant(&mut self, value: Value, line: usize) {
+        let constant = self.chunk.add_constant(value);
+        self.chunk.write_constant(constant, line);
+    }
+
+    fn pair(&mut self, first: Opcode, second: Opcode, line: usize) {
+        self.chunk.write(first, line);
+        self.chunk.write(second, line);
+    }
+}
@@ -1,11 +1,58 @@
+use std::io::Write;
+
 use crate::{
+    ast::AstParser,
     chunk::Chunk,
+    codegen::CodeGenerator,
     opcode::Opcode,
+    optimizer::{Optimizations, Optimizer},
     scanner::{CompilerError, Scanner, Token, TokenType},
+    typecheck::TypeChecker,
     value::{Obj, Value},
     vm::InterpretError,
 };
 
+/// Compiles source through the AST front end, running the given optimizations. This is what
+/// everything runs, `Compiler` is kept as the reference for the code generator.
+/// With `typecheck`, the parsed script is checked before anything is generated, and operations
+/// bound to fail are compile errors. Compile errors are written to `errors`, one per line.
+pub fn compile(
+    source: &str,
+    optimizations: Optimizations,
+    typecheck: bool,
+    errors: &mut dyn Write,
+) -> Result<Chunk, InterpretError> {
+    let mut parser = AstParser::new(source);
+    let mut script = match parser.parse() {
+        Ok(script) => script,
+        Err(error) => {
+            for diagnostic in parser.diagnostics() {
+                diagnostic.write(source, errors);
+            }
+            return Err(error);
+        }
+    };
+    if typecheck {
+        let diagnostics = TypeChecker::check(&script);
+        for diagnostic in &diagnostics {
+            diagnostic.write(source, errors);
+        }
+        if !diagnostics.is_empty() {
+            return Err(InterpretError::CompileError);
+        }
+    }
+    if optimizations.fold_constants {
+        script = Optimizer::fold_constants(script);
+    }
+
+    let mut chunk = CodeGenerator::generate(&script, optimizations.superinstructions);
+    if optimizations.peephole {
+        chunk = Optimizer::peephole(chunk, optimizations.superinstructions);
+    }
+
+    Ok(chunk)
+}
+
 pub struct Compiler<'src> {
     chunk: Option<Chunk>,
     parser: Parser<'src>,
@@ -21,7 +68,8 @@ impl<'src> Compiler<'src> {
         }
     }
 
-    pub fn compile(&mut self) -> Result<Chunk, InterpretError> {
+    /// Compile errors are written to `errors`, one per line.
+    pub fn compile(&mut self, errors: &mut dyn Write) -> Result<Chunk, InterpretError> {
         self.parser.advance(&mut self.scanner);
 
         expression(self);
@@ -32,6 +80,10 @@ impl<'src> Compiler<'src> {
             "Expect end of expression.",
         );
 
+        for diagnostic in &self.parser.diagnostics {
+            diagnostic.write(self.parser.source, errors);
+        }
+
         self.end_compiler();
 
         match self.parser.had_error {
@@ -42,12 +94,6 @@ impl<'src> Compiler<'src> {
 
     fn end_compiler(&mut self) {
         self.emit_return();
-
-        if std::env::var("DUMP").is_ok() {
-            if let Some(chunk) = &self.chunk {
-                eprintln!("{:?}", chunk);
-            }
-        }
     }
 
     fn emit_bytes<O>(&mut self, bytes: O)
@@ -93,26 +139,54 @@ impl<'src> Compiler<'src> {
     }
 }
 
-struct Parser<'src> {
-    source: &'src str,
-    current: Token,
-    previous: Token,
-    had_error: bool,
+/// A compile error, kept so that tools such as the language server can report it with a
+/// position. Errors from the scanner don't have a token, only a line.
+pub struct Diagnostic {
+    pub line: usize,
+    pub token: Option<Token>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Formats the error the way clox reports it, with the line and what it was found at.
+    pub fn format(&self, source: &str) -> String {
+        let location = match self.token {
+            Some(token) if token.token_type == TokenType::Eof => " at end".to_string(),
+            Some(token) => format!(" at '{}'", token.lexeme(source)),
+            None => String::new(),
+        };
+
+        format!("[line {}] Error{}: {}", self.line, location, self.message)
+    }
+
+    fn write(&self, source: &str, errors: &mut dyn Write) {
+        writeln!(errors, "{}", self.format(source)).expect("Could not write compile error");
+    }
+}
+
+/// Token lookahead and error reporting, shared by the single-pass compiler and the AST parser.
+pub struct Parser<'src> {
+    pub source: &'src str,
+    pub current: Token,
+    pub previous: Token,
+    pub had_error: bool,
     panic_mode: bool,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl<'src> Parser<'src> {
-    fn new(source: &'src str) -> Self {
+    pub fn new(source: &'src str) -> Self {
         Parser {
             source,
             current: Token::new(TokenType::Eof, 0, 0, 0),
             previous: Token::new(TokenType::Eof, 0, 0, 0),
             had_error: false,
             panic_mode: false,
+            diagnostics: Vec::new(),
         }
     }
 
-    fn advance(&mut self, scanner: &mut Scanner) {
+    pub fn advance(&mut self, scanner: &mut Scanner) {
         self.previous = self.current;
 
         loop {
@@ -121,12 +195,12 @@ impl<'src> Parser<'src> {
                     self.current = token;
                     break;
                 }
-                Err(error) => self.error_at_current(&error),
+                Err(error) => self.error(None, &error),
             }
         }
     }
 
-    fn consume(&mut self, scanner: &mut Scanner, token_type: TokenType, message: &str) {
+    pub fn consume(&mut self, scanner: &mut Scanner, token_type: TokenType, message: &str) {
         if self.current.token_type == token_type {
             self.advance(scanner);
             return;
@@ -138,36 +212,32 @@ impl<'src> Parser<'src> {
         });
     }
 
-    fn error_at_current(&mut self, message: &CompilerError) {
-        self.print_error_message(&self.current, message);
-        self.had_error = true;
+    pub fn error_at_current(&mut self, message: &CompilerError) {
+        self.error(Some(self.current), message);
     }
 
+    #[allow(dead_code)]
     fn error_at(&mut self, token: &Token, message: &CompilerError) {
-        self.print_error_message(token, message);
-        self.had_error = true;
+        self.error(Some(*token), message);
     }
 
-    fn print_error_message(&self, token: &Token, message: &CompilerError) {
+    fn error(&mut self, token: Option<Token>, message: &CompilerError) {
+        self.had_error = true;
+
         if self.panic_mode {
             return;
         }
 
-        if token.token_type == TokenType::Eof {
-            eprintln!("[line {}] Error at end: {}", token.line, message.message);
-        } else {
-            eprintln!(
-                "[line {}] Error at '{}': {}",
-                token.line,
-                token.lexeme(self.source),
-                message.message
-            );
-        }
+        self.diagnostics.push(Diagnostic {
+            line: token.map_or(message.line, |token| token.line),
+            token,
+            message: message.message.clone(),
+        });
     }
 }
 
 #[derive(PartialOrd, PartialEq)]
-enum Precedence {
+pub enum Precedence {
     None,
     Assignment, // =
     Or,         // or
@@ -182,7 +252,7 @@ enum Precedence {
 }
 
 impl Precedence {
-    fn next(&self) -> Precedence {
+    pub fn next(&self) -> Precedence {
         match self {
             Precedence::None => Precedence::None,
             Precedence::Assignment => Precedence::Or,
@@ -199,10 +269,10 @@ impl Precedence {
     }
 }
 
-struct ParseRule {
+pub struct ParseRule {
     prefix: Option<fn(&mut Compiler)>,
     infix: Option<fn(&mut Compiler)>,
-    precedence: Precedence,
+    pub precedence: Precedence,
 }
 
 fn parse_precedence(compiler: &mut Compiler, precedence: Precedence) {
@@ -228,7 +298,7 @@ fn parse_precedence(compiler: &mut Compiler, precedence: Precedence) {
 }
 
 #[rustfmt::skip]
-fn get_rule(token_type: TokenType) -> ParseRule {
+pub fn get_rule(token_type: TokenType) -> ParseRule {
     match token_type {
          TokenType::LeftParen => ParseRule { prefix: Some(grouping), infix: None, precedence: Precedence::None },
         TokenType::RightParen => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
@@ -268,6 +338,7 @@ fn get_rule(token_type: TokenType) -> ParseRule {
               TokenType::True => ParseRule { prefix: Some(literal), infix: None, precedence: Precedence::None },
                TokenType::Var => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
              TokenType::While => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+           TokenType::Comment => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
              TokenType::Error => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                TokenType::Eof => ParseRule { prefix: None, infix: None, precedence: Precedence::None },
     }
@@ -326,7 +397,7 @@ fn binary(compiler: &mut Compiler) {
 fn number(compiler: &mut Compiler) {
     let value = compiler.parser.previous.lexeme(compiler.parser.source);
     let value = value.parse::<f64>().unwrap();
-    let constant = compiler.make_constant(Value::Number(value));
+    let constant = compiler.make_constant(Value::number(value));
 
     compiler.emit_constant(constant);
 }
@@ -343,7 +414,7 @@ fn literal(compiler: &mut Compiler) {
 fn string(compiler: &mut Compiler) {
     let value = compiler.parser.previous.lexeme(compiler.parser.source);
     let value = value[1..value.len() - 1].to_string();
-    let constant = compiler.make_constant(Value::Obj(Obj::String(value)));
+    let constant = compiler.make_constant(Value::obj(Obj::String(value)));
 
     compiler.emit_constant(constant);
 }
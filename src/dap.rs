@@ -0,0 +1,441 @@
+use std::io::{BufRead, Write};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debugger::{Breakpoint, Debugger},
+    dissasembler::Dissasembler,
+    load_chunk,
+    optimizer::Optimizations,
+    output::SharedBuffer,
+    transport::{read_message, write_message},
+    vm::{ExecutionState, Vm},
+};
+
+// There is only ever one thread, one stack frame and one scope, so these are fixed.
+const THREAD_ID: u64 = 1;
+const FRAME_ID: u64 = 0;
+const STACK_REFERENCE: u64 = 1;
+
+// The exit code reported for a runtime error, EX_SOFTWARE as the CLI exits with.
+const RUNTIME_ERROR_EXIT_CODE: i32 = 70;
+
+struct Session {
+    debugger: Debugger<'static>,
+    program: String,
+    stop_on_entry: bool,
+    finished: bool,
+}
+
+/// A Debug Adapter Protocol server, speaking Content-Length framed JSON over the given reader
+/// and writer. Execution is driven through the same `Debugger` as the `--debug` prompt.
+pub struct DapServer<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    seq: u64,
+    session: Option<Session>,
+    breakpoint_lines: Vec<usize>,
+    configured: bool,
+    program_output: SharedBuffer,
+    program_errors: SharedBuffer,
+}
+
+impl<R: BufRead, W: Write> DapServer<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        DapServer {
+            input,
+            output,
+            seq: 1,
+            session: None,
+            breakpoint_lines: Vec::new(),
+            configured: false,
+            program_output: SharedBuffer::default(),
+            program_errors: SharedBuffer::default(),
+        }
+    }
+
+    pub fn run(&mut self) -> std::io::Result<()> {
+        while let Some(message) = read_message(&mut self.input)? {
+            // NOTE: There is no request to answer without a body to read its seq from, so the
+            //       client is told in its console and the adapter reads on.
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    let body = json!({
+                        "category": "console",
+                        "output": format!("Ignoring a message that isn't JSON: {}\n", error),
+                    });
+                    self.send_event("output", body)?;
+                    continue;
+                }
+            };
+            if message["type"] != "request" {
+                continue;
+            }
+
+            if !self.handle_request(&message)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns false once the client has asked to disconnect.
+    fn handle_request(&mut self, request: &Json) -> std::io::Result<bool> {
+        let arguments = &request["arguments"];
+
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsSteppingGranularity": true,
+                });
+                self.respond(request, capabilities)?;
+            }
+            "launch" => self.launch(request, arguments)?,
+            "setBreakpoints" => self.set_breakpoints(request, arguments)?,
+            "configurationDone" => {
+                self.respond(request, json!({}))?;
+                self.configured = true;
+                self.start()?;
+            }
+            "threads" => {
+                let threads = json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] });
+                self.respond(request, threads)?;
+            }
+            "stackTrace" => self.stack_trace(request)?,
+            "scopes" => {
+                let scopes = json!({
+                    "scopes": [{
+                        "name": "Stack",
+                        "variablesReference": STACK_REFERENCE,
+                        "expensive": false,
+                    }]
+                });
+                self.respond(request, scopes)?;
+            }
+            "variables" => self.variables(request, arguments)?,
+            "evaluate" => self.evaluate(request, arguments)?,
+            "next" | "stepIn" => {
+                let how = match arguments["granularity"].as_str() {
+                    Some("instruction") => Debugger::step_instruction,
+                    _ => Debugger::step_line,
+                };
+                self.resume(request, how, "step")?;
+            }
+            // NOTE: Everything runs in the one top level frame, so stepping out of it runs to
+            //       the end of the program, stopping for any breakpoints along the way.
+            "stepOut" => self.resume(request, Debugger::continue_to_breakpoint, "step")?,
+            "continue" => {
+                self.resume(request, Debugger::continue_to_breakpoint, "breakpoint")?;
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, json!({}))?;
+                return Ok(false);
+            }
+            command => {
+                self.respond_error(request, &format!("Unsupported request '{}'", command))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn launch(&mut self, request: &Json, arguments: &Json) -> std::io::Result<()> {
+        let program = match arguments["program"].as_str() {
+            Some(program) => program.to_string(),
+            None => return self.respond_error(request, "Expected a 'program' to launch"),
+        };
+
+        // NOTE: Constants are never folded while debugging, so that every operation in the source
+        //       can still be stepped through. Compile errors go with the failed response, as the
+        //       client never sees the server's stderr.
+        let chunk = match load_chunk(
+            &program,
+            Optimizations::none(),
+            false,
+            &mut self.program_errors,
+        ) {
+            Ok((chunk, _)) => chunk,
+            Err(error) => {
+                let errors = self.program_errors.take();
+                let message = match errors.is_empty() {
+                    true => format!("Could not load '{}': {}", program, error),
+                    false => String::from_utf8_lossy(&errors).trim_end().to_string(),
+                };
+                return self.respond_error(request, &message);
+            }
+        };
+
+        let mut vm = Vm::new();
+        vm.set_output(Box::new(self.program_output.clone()));
+        vm.set_error_output(Box::new(self.program_errors.clone()));
+        if let Err(error) = vm.load(chunk) {
+            let message = format!("Could not load '{}': {}", program, error);
+            return self.respond_error(request, &message);
+        }
+
+        self.session = Some(Session {
+            debugger: Debugger::new(vm, None),
+            program,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            finished: false,
+        });
+        self.apply_breakpoints();
+
+        self.respond(request, json!({}))?;
+
+        // NOTE: Breakpoints can only be checked against the chunk once it has been loaded, so the
+        //       client isn't told to send them until now.
+        self.send_event("initialized", json!({}))?;
+
+        if self.configured {
+            self.start()?;
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> std::io::Result<()> {
+        let session = match &self.session {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+
+        if session.stop_on_entry {
+            return self.send_stopped("entry");
+        }
+        if session.debugger.at_breakpoint() {
+            return self.send_stopped("breakpoint");
+        }
+
+        let state = self.session_mut().debugger.continue_to_breakpoint();
+        self.after_resume(state, "breakpoint")
+    }
+
+    fn set_breakpoints(&mut self, request: &Json, arguments: &Json) -> std::io::Result<()> {
+        self.breakpoint_lines = arguments["breakpoints"]
+            .as_array()
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let breakpoints: Vec<Json> = self
+            .breakpoint_lines
+            .iter()
+            .map(|line| {
+                let verified = match &self.session {
+                    Some(session) => session.debugger.has_code_on_line(*line),
+                    None => false,
+                };
+                json!({ "verified": verified, "line": line })
+            })
+            .collect();
+
+        self.apply_breakpoints();
+        self.respond(request, json!({ "breakpoints": breakpoints }))
+    }
+
+    fn apply_breakpoints(&mut self) {
+        let lines = self.breakpoint_lines.clone();
+        if let Some(session) = &mut self.session {
+            let breakpoints = lines.into_iter().map(Breakpoint::Line).collect();
+            session.debugger.set_breakpoints(breakpoints);
+        }
+    }
+
+    fn stack_trace(&mut self, request: &Json) -> std::io::Result<()> {
+        let session = match self.running_session() {
+            Some(session) => session,
+            None => return self.respond_error(request, "The program is not running"),
+        };
+
+        let vm = session.debugger.vm();
+        let name = std::path::Path::new(&session.program)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let frame = json!({
+            "id": FRAME_ID,
+            "name": vm.chunk().unwrap().name,
+            "line": session.debugger.current_line(),
+            "column": 1,
+            "source": { "name": name, "path": session.program },
+            "instructionPointerReference": vm.ip().to_string(),
+        });
+        self.respond(request, json!({ "stackFrames": [frame], "totalFrames": 1 }))
+    }
+
+    fn variables(&mut self, request: &Json, arguments: &Json) -> std::io::Result<()> {
+        let session = match self.running_session() {
+            Some(session) => session,
+            None => return self.respond_error(request, "The program is not running"),
+        };
+
+        if arguments["variablesReference"].as_u64() != Some(STACK_REFERENCE) {
+            return self.respond(request, json!({ "variables": [] }));
+        }
+
+        let variables: Vec<Json> = session
+            .debugger
+            .vm()
+            .stack()
+            .iter()
+            .enumerate()
+            .map(|(slot, value)| {
+                json!({
+                    "name": format!("[{}]", slot),
+                    "value": Dissasembler::format_constant(value),
+                    "type": value.type_name(),
+                    "variablesReference": 0,
+                })
+            })
+            .collect();
+
+        self.respond(request, json!({ "variables": variables }))
+    }
+
+    fn evaluate(&mut self, request: &Json, arguments: &Json) -> std::io::Result<()> {
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+
+        let session = match &mut self.session {
+            Some(session) if !session.finished => session,
+            _ => return self.respond_error(request, "The program is not running"),
+        };
+
+        match session.debugger.vm_mut().evaluate(expression) {
+            Ok(value) => {
+                let body = json!({
+                    "result": Dissasembler::format_constant(&value),
+                    "type": value.type_name(),
+                    "variablesReference": 0,
+                });
+                self.respond(request, body)
+            }
+            // NOTE: The errors the VM wrote say more than the error itself, and belong to this
+            //       request rather than to the program's output.
+            Err(error) => {
+                let errors = self.program_errors.take();
+                let message = match errors.is_empty() {
+                    true => error.to_string(),
+                    false => String::from_utf8_lossy(&errors).trim_end().to_string(),
+                };
+                self.respond_error(request, &message)
+            }
+        }
+    }
+
+    fn resume(
+        &mut self,
+        request: &Json,
+        how: fn(&mut Debugger<'static>) -> ExecutionState,
+        reason: &str,
+    ) -> std::io::Result<()> {
+        if self.running_session().is_none() {
+            return self.respond_error(request, "The program is not running");
+        }
+
+        let body = match request["command"] == "continue" {
+            true => json!({ "allThreadsContinued": true }),
+            false => json!({}),
+        };
+        self.respond(request, body)?;
+
+        let state = how(&mut self.session_mut().debugger);
+        self.after_resume(state, reason)
+    }
+
+    fn after_resume(&mut self, state: ExecutionState, reason: &str) -> std::io::Result<()> {
+        self.flush_program_output()?;
+
+        match state {
+            ExecutionState::Running => self.send_stopped(reason),
+            ExecutionState::Finished => {
+                let session = self.session_mut();
+                session.finished = true;
+                let exit_code = match session.debugger.failed() {
+                    true => RUNTIME_ERROR_EXIT_CODE,
+                    false => 0,
+                };
+                self.send_event("exited", json!({ "exitCode": exit_code }))?;
+                self.send_event("terminated", json!({}))
+            }
+        }
+    }
+
+    fn send_stopped(&mut self, reason: &str) -> std::io::Result<()> {
+        let body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        self.send_event("stopped", body)
+    }
+
+    fn flush_program_output(&mut self) -> std::io::Result<()> {
+        for (buffer, category) in [
+            (self.program_output.clone(), "stdout"),
+            (self.program_errors.clone(), "stderr"),
+        ] {
+            let output = buffer.take();
+            if output.is_empty() {
+                continue;
+            }
+
+            let body = json!({
+                "category": category,
+                "output": String::from_utf8_lossy(&output),
+            });
+            self.send_event("output", body)?;
+        }
+
+        Ok(())
+    }
+
+    fn running_session(&self) -> Option<&Session> {
+        self.session.as_ref().filter(|session| !session.finished)
+    }
+
+    fn session_mut(&mut self) -> &mut Session {
+        self.session.as_mut().unwrap()
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> std::io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn respond_error(&mut self, request: &Json, message: &str) -> std::io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn send_event(&mut self, event: &str, body: Json) -> std::io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Json) -> std::io::Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+
+        write_message(&mut self.output, &message)
+    }
+}
@@ -0,0 +1,377 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    chunk::Chunk,
+    dissasembler::Dissasembler,
+    opcode::Opcode,
+    value::Value,
+    vm::{ExecutionState, Vm},
+};
+
+const HELP: &str = "\
+Commands:
+  break <line>        Stop before the first instruction of a source line   (b)
+  break *<offset>     Stop before the instruction at a bytecode offset
+  break               List breakpoints
+  delete [n]          Delete breakpoint n, or all of them                  (d)
+  step                Run to the next source line                          (s)
+  stepi               Run a single instruction                             (si)
+  next                The same as step until there are calls               (n)
+  nexti               The same as stepi until there are calls              (ni)
+  finish              The same as continue until there are calls
+  continue            Run until a breakpoint or the end of the program     (c)
+  stack               Show the values on the stack
+  print <expr>        Evaluate an expression in the paused VM              (p)
+  watch <expr>        Evaluate an expression every time execution stops
+  unwatch [n]         Delete watch n, or all of them
+  list                Show the source around the current line              (l)
+  quit                Stop debugging                                       (q)";
+
+pub enum Breakpoint {
+    Line(usize),
+    Offset(usize),
+}
+
+pub struct Debugger<'src> {
+    vm: Vm,
+    source: Option<&'src str>,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<String>,
+    finished: bool,
+    failed: bool,
+}
+
+impl<'src> Debugger<'src> {
+    /// Takes a VM that already has a chunk loaded, see `Vm::load`. The source is optional, as
+    /// chunks loaded from bytecode or assembly don't have any.
+    pub fn new(vm: Vm, source: Option<&'src str>) -> Self {
+        Debugger {
+            vm,
+            source,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            finished: false,
+            failed: false,
+        }
+    }
+
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> std::io::Result<()> {
+        writeln!(output, "Type 'help' for a list of commands.")?;
+        self.show_location(&mut output)?;
+
+        loop {
+            write!(output, "(loxdb) ")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+
+            let line = line.trim();
+            let (command, argument) = match line.split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line, ""),
+            };
+
+            match command {
+                "" => {}
+                "help" | "h" => writeln!(output, "{}", HELP)?,
+                "break" | "b" => self.add_breakpoint(argument, &mut output)?,
+                "delete" | "d" => self.delete_breakpoint(argument, &mut output)?,
+                "step" | "s" | "next" | "n" => self.resume(&mut output, Self::step_line)?,
+                "stepi" | "si" | "nexti" | "ni" => {
+                    self.resume(&mut output, Self::step_instruction)?
+                }
+                "finish" => self.resume(&mut output, Self::finish)?,
+                "continue" | "c" => self.resume(&mut output, Self::continue_to_breakpoint)?,
+                "stack" => self.show_stack(&mut output)?,
+                "print" | "p" => self.print_expression(argument, &mut output)?,
+                "watch" => self.add_watch(argument, &mut output)?,
+                "unwatch" => self.delete_watch(argument, &mut output)?,
+                "list" | "l" => self.list_source(&mut output)?,
+                "quit" | "q" => return Ok(()),
+                _ => writeln!(output, "Unknown command '{}'. Try 'help'.", command)?,
+            }
+        }
+    }
+
+    fn resume<W: Write>(
+        &mut self,
+        output: &mut W,
+        how: fn(&mut Self) -> ExecutionState,
+    ) -> std::io::Result<()> {
+        if self.finished {
+            return writeln!(output, "The program is not running.");
+        }
+
+        if how(self) == ExecutionState::Finished {
+            self.finished = true;
+            return match self.failed {
+                true => writeln!(output, "Program stopped on a runtime error."),
+                false => writeln!(output, "Program finished."),
+            };
+        }
+
+        self.show_location(output)
+    }
+
+    pub fn step_instruction(&mut self) -> ExecutionState {
+        match self.vm.step() {
+            Ok(state) => state,
+            // NOTE: The VM has already reported the error, and there is nothing left to run.
+            Err(_) => {
+                self.failed = true;
+                ExecutionState::Finished
+            }
+        }
+    }
+
+    /// Whether the program has stopped on a runtime error.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn step_line(&mut self) -> ExecutionState {
+        let line = self.current_line();
+        loop {
+            if self.step_instruction() == ExecutionState::Finished {
+                return ExecutionState::Finished;
+            }
+            if self.current_line() != line || self.at_breakpoint() {
+                return ExecutionState::Running;
+            }
+        }
+    }
+
+    // NOTE: Everything runs in the one top level frame for now, so stepping out of it is the
+    //       same as running to the end, stopping for any breakpoints along the way.
+    fn finish(&mut self) -> ExecutionState {
+        self.continue_to_breakpoint()
+    }
+
+    pub fn continue_to_breakpoint(&mut self) -> ExecutionState {
+        loop {
+            if self.step_instruction() == ExecutionState::Finished {
+                return ExecutionState::Finished;
+            }
+            if self.at_breakpoint() {
+                return ExecutionState::Running;
+            }
+        }
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<Breakpoint>) {
+        self.breakpoints = breakpoints;
+    }
+
+    pub fn has_code_on_line(&self, line: usize) -> bool {
+        self.vm
+            .chunk()
+            .unwrap()
+            .lines
+            .iter()
+            .any(|(l, _)| *l == line)
+    }
+
+    pub fn at_breakpoint(&self) -> bool {
+        let ip = self.vm.ip();
+        let lines = &self.vm.chunk().unwrap().lines;
+
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Offset(offset) => *offset == ip,
+            Breakpoint::Line(line) => lines.contains(&(*line, ip)),
+        })
+    }
+
+    pub fn current_line(&self) -> usize {
+        // NOTE: `line_for_instruction_n` expects the ip to have already moved past the opcode.
+        self.vm
+            .chunk()
+            .unwrap()
+            .line_for_instruction_n(self.vm.ip() + 1)
+    }
+
+    fn add_breakpoint<W: Write>(&mut self, argument: &str, output: &mut W) -> std::io::Result<()> {
+        if argument.is_empty() {
+            if self.breakpoints.is_empty() {
+                return writeln!(output, "No breakpoints.");
+            }
+            for (n, breakpoint) in self.breakpoints.iter().enumerate() {
+                match breakpoint {
+                    Breakpoint::Line(line) => writeln!(output, "{}: line {}", n, line)?,
+                    Breakpoint::Offset(offset) => writeln!(output, "{}: *{:04}", n, offset)?,
+                }
+            }
+            return Ok(());
+        }
+
+        let chunk = self.vm.chunk().unwrap();
+        let breakpoint = if let Some(offset) = argument.strip_prefix('*') {
+            match offset.parse::<usize>() {
+                Ok(offset) if Self::is_instruction_start(chunk, offset) => {
+                    Breakpoint::Offset(offset)
+                }
+                Ok(offset) => return writeln!(output, "No instruction at offset {:04}.", offset),
+                Err(_) => return writeln!(output, "Expected an offset, found '{}'.", offset),
+            }
+        } else {
+            match argument.parse::<usize>() {
+                Ok(line) if self.has_code_on_line(line) => Breakpoint::Line(line),
+                Ok(line) => return writeln!(output, "No code on line {}.", line),
+                Err(_) => return writeln!(output, "Expected a line, found '{}'.", argument),
+            }
+        };
+
+        writeln!(output, "Breakpoint {} set.", self.breakpoints.len())?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    // NOTE: The walk stops at the end of the code, as offsets past it are whatever was typed.
+    fn is_instruction_start(chunk: &Chunk, target: usize) -> bool {
+        let mut offset = 0;
+        while offset < target && offset < chunk.code.len() {
+            offset += 1 + Opcode::from(chunk.code[offset]).operand_len();
+        }
+        offset == target && offset < chunk.code.len()
+    }
+
+    fn delete_breakpoint<W: Write>(
+        &mut self,
+        argument: &str,
+        output: &mut W,
+    ) -> std::io::Result<()> {
+        if argument.is_empty() {
+            self.breakpoints.clear();
+            return writeln!(output, "Deleted all breakpoints.");
+        }
+
+        match argument.parse::<usize>() {
+            Ok(n) if n < self.breakpoints.len() => {
+                self.breakpoints.remove(n);
+                writeln!(output, "Deleted breakpoint {}.", n)
+            }
+            _ => writeln!(output, "No breakpoint '{}'.", argument),
+        }
+    }
+
+    fn add_watch<W: Write>(&mut self, argument: &str, output: &mut W) -> std::io::Result<()> {
+        if argument.is_empty() {
+            return writeln!(output, "Expected an expression to watch.");
+        }
+
+        self.watches.push(argument.to_string());
+        self.show_watch(self.watches.len() - 1, output)
+    }
+
+    fn delete_watch<W: Write>(&mut self, argument: &str, output: &mut W) -> std::io::Result<()> {
+        if argument.is_empty() {
+            self.watches.clear();
+            return writeln!(output, "Deleted all watches.");
+        }
+
+        match argument.parse::<usize>() {
+            Ok(n) if n < self.watches.len() => {
+                self.watches.remove(n);
+                writeln!(output, "Deleted watch {}.", n)
+            }
+            _ => writeln!(output, "No watch '{}'.", argument),
+        }
+    }
+
+    fn print_expression<W: Write>(
+        &mut self,
+        argument: &str,
+        output: &mut W,
+    ) -> std::io::Result<()> {
+        match self.vm.evaluate(argument) {
+            Ok(value) => writeln!(output, "{}", format_value(&value)),
+            Err(error) => writeln!(output, "Could not evaluate '{}': {}", argument, error),
+        }
+    }
+
+    fn show_watch<W: Write>(&mut self, n: usize, output: &mut W) -> std::io::Result<()> {
+        let expression = self.watches[n].clone();
+        write!(output, "watch {}: {} = ", n, expression)?;
+        match self.vm.evaluate(&expression) {
+            Ok(value) => writeln!(output, "{}", format_value(&value)),
+            Err(error) => writeln!(output, "<{}>", error),
+        }
+    }
+
+    fn show_location<W: Write>(&mut self, output: &mut W) -> std::io::Result<()> {
+        let line = self.current_line();
+        let mut instruction = String::new();
+        Dissasembler::disassemble_instruction(
+            self.vm.chunk().unwrap(),
+            &mut self.vm.ip(),
+            &mut instruction,
+        )
+        .expect("Could not decode instruction");
+
+        if let Some(text) = self.source_line(line) {
+            writeln!(output, "{:4} | {}", line, text)?;
+        }
+        write!(output, "{}", instruction)?;
+
+        for n in 0..self.watches.len() {
+            self.show_watch(n, output)?;
+        }
+
+        Ok(())
+    }
+
+    fn show_stack<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let stack = self.vm.stack();
+        if stack.is_empty() {
+            return writeln!(output, "The stack is empty.");
+        }
+
+        for (slot, value) in stack.iter().enumerate().rev() {
+            writeln!(output, "[{:3}] {}", slot, format_value(value))?;
+        }
+        Ok(())
+    }
+
+    fn list_source<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let current = self.current_line();
+        if self.source.is_none() {
+            return writeln!(output, "No source available, stopped on line {}.", current);
+        }
+
+        let first = current.saturating_sub(3).max(1);
+        for line in first..=current + 3 {
+            if let Some(text) = self.source_line(line) {
+                let marker = if line == current { "->" } else { "  " };
+                writeln!(output, "{} {:4} | {}", marker, line, text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn source_line(&self, line: usize) -> Option<&'src str> {
+        self.source?.lines().nth(line.checked_sub(1)?)
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    format!(
+        "{} ({})",
+        Dissasembler::format_constant(value),
+        value.type_name()
+    )
+}
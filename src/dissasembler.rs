@@ -1,13 +1,14 @@
 use std::fmt::Write;
 
 use crate::chunk::Chunk;
-use crate::Opcode;
+use crate::opcode::Opcode;
+use crate::value::{Obj, Value, ValueKind};
 
 pub struct Dissasembler {}
 
 impl Dissasembler {
     pub fn disassemble<W: Write>(chunk: &Chunk, f: &mut W) -> std::fmt::Result {
-        println!("== {} ==", chunk.name);
+        writeln!(f, "== {} ==", chunk.name)?;
 
         let mut offset = 0;
         while offset < chunk.code.len() {
@@ -75,16 +76,35 @@ impl Dissasembler {
             Opcode::Less => {
                 Self::disassemble_simple_instruction("Less", f)?;
             }
+            Opcode::NotEqual => {
+                Self::disassemble_simple_instruction("NotEqual", f)?;
+            }
+            Opcode::GreaterEqual => {
+                Self::disassemble_simple_instruction("GreaterEqual", f)?;
+            }
+            Opcode::LessEqual => {
+                Self::disassemble_simple_instruction("LessEqual", f)?;
+            }
+            Opcode::AddConstant => {
+                Self::dissassemble_constant_instruction(chunk, offset, "AddConstant", f)?;
+            }
+            Opcode::ReturnNil => {
+                Self::disassemble_simple_instruction("ReturnNil", f)?;
+            }
         }
 
         Ok(())
     }
 
-    pub fn trace_instruction(chunk: &Chunk, offset: &mut usize) {
+    pub fn trace_instruction<W: std::io::Write>(
+        chunk: &Chunk,
+        offset: &mut usize,
+        out: &mut W,
+    ) -> std::io::Result<()> {
         let mut output = String::new();
         Self::disassemble_instruction(chunk, offset, &mut output)
             .expect("Could not trace instruction");
-        print!("{}", output);
+        write!(out, "{}", output)
     }
 
     fn write_line_info<W: Write>(chunk: &Chunk, offset: usize, f: &mut W) -> std::fmt::Result {
@@ -115,7 +135,9 @@ impl Dissasembler {
         writeln!(
             f,
             "{:<16} {:4} '{}'",
-            name, constant, chunk.constants[constant as usize]
+            name,
+            constant,
+            Self::format_constant(&chunk.constants[constant as usize])
         )
     }
 
@@ -135,7 +157,36 @@ impl Dissasembler {
         writeln!(
             f,
             "{:<16} {:4} '{}'",
-            name, constant, chunk.constants[constant as usize]
+            name,
+            constant,
+            Self::format_constant(&chunk.constants[constant as usize])
         )
     }
+
+    // NOTE: Strings are quoted so that a listing can be read back by the assembler without
+    //       confusing the string "1" with the number 1. Characters that would end the line, the
+    //       quotes around the constant or the string itself are escaped with a backslash.
+    pub fn format_constant(value: &Value) -> String {
+        match value.kind() {
+            ValueKind::Obj(Obj::String(s)) => {
+                let mut quoted = String::with_capacity(s.len() + 2);
+                quoted.push('"');
+                for c in s.chars() {
+                    match c {
+                        '\n' => quoted.push_str("\\n"),
+                        '\r' => quoted.push_str("\\r"),
+                        '\t' => quoted.push_str("\\t"),
+                        '\\' | '\'' | '"' => {
+                            quoted.push('\\');
+                            quoted.push(c);
+                        }
+                        _ => quoted.push(c),
+                    }
+                }
+                quoted.push('"');
+                quoted
+            }
+            _ => value.to_string(),
+        }
+    }
 }
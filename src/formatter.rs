@@ -0,0 +1,347 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use crate::{
+    ast::{AstParser, Expr, ExprKind, Span},
+    scanner::{Scanner, Token, TokenType},
+};
+
+const MAX_WIDTH: usize = 80;
+const INDENT_WIDTH: usize = 4;
+
+#[derive(Debug)]
+pub enum FormatError {
+    CompileError,
+    NotIdempotent,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::CompileError => write!(f, "The source does not compile"),
+            FormatError::NotIdempotent => {
+                write!(f, "Formatting is not idempotent for this source")
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+// A small Wadler style document. Each group is printed on one line if it fits, otherwise every
+// `Line` directly inside it becomes a newline.
+enum Doc {
+    Text(String),
+    // A space, unless a comment has already ended the line.
+    Space,
+    // A space when the enclosing group is flat, otherwise a newline.
+    Line,
+    List(Vec<Doc>),
+    Group(Vec<Doc>),
+    Indent(Vec<Doc>),
+    // A comment that had a line to itself, and one that followed a token on the same line.
+    OwnLineComment(String),
+    TrailingComment(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+pub struct Formatter<'src> {
+    source: &'src str,
+    tokens: Vec<Token>,
+    leading: HashMap<usize, Vec<Token>>,
+    trailing: HashMap<usize, Token>,
+    end: Vec<Token>,
+}
+
+impl<'src> Formatter<'src> {
+    /// Reformats Lox source to the canonical style: binary operators surrounded by single
+    /// spaces, unary operators against their operand, and expressions longer than the line
+    /// width broken before their operators, lowest precedence first. Comments are kept, blank
+    /// lines are not.
+    ///
+    /// Formatting is idempotent. The output is formatted a second time, and if that would change
+    /// it the source is left alone and `FormatError::NotIdempotent` is returned.
+    pub fn format(source: &'src str) -> Result<String, FormatError> {
+        let formatted = Self::format_once(source)?;
+        match Self::format_once(&formatted) {
+            Ok(again) if again == formatted => Ok(formatted),
+            _ => Err(FormatError::NotIdempotent),
+        }
+    }
+
+    fn format_once(source: &str) -> Result<String, FormatError> {
+        let script = AstParser::new(source)
+            .parse()
+            .map_err(|_| FormatError::CompileError)?;
+
+        let mut formatter = Formatter::new(source);
+
+        // NOTE: Comments before the first token go above everything else, so that they don't
+        //       stop the expression from being printed on one line.
+        let first = formatter.tokens[0].start;
+        let mut docs: Vec<Doc> = formatter
+            .leading
+            .remove(&first)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|comment| Doc::OwnLineComment(formatter.text(comment.into())))
+            .collect();
+        docs.push(formatter.expression(&script.body));
+        for comment in &formatter.end {
+            docs.push(Doc::OwnLineComment(formatter.text((*comment).into())));
+        }
+
+        let mut output = print(&Doc::List(docs));
+        output.push('\n');
+        Ok(output)
+    }
+
+    fn new(source: &'src str) -> Self {
+        // NOTE: The source has already parsed, so scanning it again can't fail.
+        let mut scanner = Scanner::new(source);
+        let mut tokens = Vec::new();
+        loop {
+            match scanner.scan_token() {
+                Ok(token) if token.token_type == TokenType::Eof => break,
+                Ok(token) => tokens.push(token),
+                Err(_) => unreachable!(),
+            }
+        }
+        let comments = scanner.comments().to_vec();
+
+        let mut formatter = Formatter {
+            source,
+            tokens,
+            leading: HashMap::new(),
+            trailing: HashMap::new(),
+            end: Vec::new(),
+        };
+        formatter.attach_comments(&comments);
+        formatter
+    }
+
+    // A comment on the same line as the token before it trails that token, any other comment
+    // leads the token after it. Tokens are keyed by their start offset.
+    fn attach_comments(&mut self, comments: &[Token]) {
+        for comment in comments {
+            let previous = self
+                .tokens
+                .iter()
+                .rev()
+                .find(|token| token.start < comment.start);
+            let next = self.tokens.iter().find(|token| token.start > comment.start);
+
+            match (previous, next) {
+                (Some(previous), _) if previous.line == comment.line => {
+                    self.trailing.insert(previous.start, *comment);
+                }
+                (_, Some(next)) => self.leading.entry(next.start).or_default().push(*comment),
+                (_, None) => self.end.push(*comment),
+            }
+        }
+    }
+
+    fn expression(&self, expr: &Expr) -> Doc {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Invalid => self.token(expr.span),
+            ExprKind::Unary {
+                operator_span,
+                operand,
+                ..
+            } => Doc::List(vec![self.token(*operator_span), self.expression(operand)]),
+            ExprKind::Grouping(inner) => {
+                let span = expr.span;
+                let open = Span { length: 1, ..span };
+                let close = Span {
+                    start: span.start + span.length - 1,
+                    length: 1,
+                    ..span
+                };
+                Doc::List(vec![
+                    self.token(open),
+                    self.expression(inner),
+                    self.token(close),
+                ])
+            }
+            ExprKind::Binary { .. } => self.binary(expr),
+        }
+    }
+
+    // A run of operators with the same precedence, such as `a + b - c`, forms one group, so it
+    // either fits on a line or breaks before every one of its operators.
+    fn binary(&self, expr: &Expr) -> Doc {
+        let precedence = match &expr.kind {
+            ExprKind::Binary { operator, .. } => operator.precedence(),
+            _ => unreachable!(),
+        };
+
+        let mut operations = Vec::new();
+        let mut left = expr;
+        while let ExprKind::Binary {
+            left: l,
+            operator,
+            operator_span,
+            right,
+        } = &left.kind
+        {
+            if operator.precedence() != precedence {
+                break;
+            }
+            operations.push((*operator_span, right));
+            left = l;
+        }
+
+        let mut rest = Vec::new();
+        for (operator_span, right) in operations.into_iter().rev() {
+            rest.push(Doc::Line);
+            rest.push(self.token(operator_span));
+            rest.push(Doc::Space);
+            rest.push(self.expression(right));
+        }
+
+        Doc::Group(vec![self.expression(left), Doc::Indent(rest)])
+    }
+
+    fn token(&self, span: Span) -> Doc {
+        let mut docs: Vec<Doc> = self
+            .leading
+            .get(&span.start)
+            .into_iter()
+            .flatten()
+            .map(|comment| Doc::OwnLineComment(self.text((*comment).into())))
+            .collect();
+
+        docs.push(Doc::Text(self.text(span)));
+
+        if let Some(comment) = self.trailing.get(&span.start) {
+            docs.push(Doc::TrailingComment(self.text((*comment).into())));
+        }
+
+        Doc::List(docs)
+    }
+
+    fn text(&self, span: Span) -> String {
+        span.lexeme(self.source).trim_end().to_string()
+    }
+}
+
+struct Printer {
+    output: String,
+    column: usize,
+    line_is_empty: bool,
+    // Set after a comment, which always runs to the end of its line.
+    needs_newline: bool,
+}
+
+impl Printer {
+    fn newline(&mut self, indent: usize) {
+        self.output.push('\n');
+        self.output.push_str(&" ".repeat(indent));
+        self.column = indent;
+        self.line_is_empty = true;
+        self.needs_newline = false;
+    }
+
+    fn write(&mut self, indent: usize, text: &str) {
+        if self.needs_newline {
+            self.newline(indent);
+        }
+        self.output.push_str(text);
+        self.column += text.chars().count();
+        self.line_is_empty = false;
+    }
+}
+
+fn print(doc: &Doc) -> String {
+    let mut printer = Printer {
+        output: String::new(),
+        column: 0,
+        line_is_empty: true,
+        needs_newline: false,
+    };
+
+    let mut commands = vec![(0, Mode::Break, doc)];
+    while let Some((indent, mode, doc)) = commands.pop() {
+        match doc {
+            Doc::Text(text) => printer.write(indent, text),
+            Doc::Space => {
+                if !printer.needs_newline {
+                    printer.write(indent, " ");
+                }
+            }
+            Doc::Line => match mode {
+                Mode::Flat => printer.write(indent, " "),
+                Mode::Break => printer.newline(indent),
+            },
+            Doc::List(docs) => commands.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            Doc::Indent(docs) => {
+                let indent = indent + INDENT_WIDTH;
+                commands.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Group(docs) => {
+                let remaining = MAX_WIDTH as isize - printer.column as isize;
+                let mode = match mode == Mode::Flat || fits(remaining, doc, &commands) {
+                    true => Mode::Flat,
+                    false => Mode::Break,
+                };
+                commands.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::OwnLineComment(text) => {
+                if !printer.line_is_empty {
+                    printer.newline(indent);
+                }
+                printer.write(indent, text);
+                printer.needs_newline = true;
+            }
+            Doc::TrailingComment(text) => {
+                printer.write(indent, &format!(" {}", text));
+                printer.needs_newline = true;
+            }
+        }
+    }
+
+    printer.output
+}
+
+// Whether a group fits flat in the remaining width, along with whatever follows it up to the
+// next line break. A comment ends its line, so a group only fits around one if nothing else
+// follows it on that line.
+fn fits(mut remaining: isize, group: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut commands = vec![(Mode::Flat, group)];
+    let mut rest = rest.iter().rev();
+    let mut line_ended = false;
+
+    loop {
+        let (mode, doc) = match commands.pop() {
+            Some(command) => command,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(_) | Doc::Space if line_ended => return false,
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Space => remaining -= 1,
+            Doc::Line => match mode {
+                Mode::Flat if line_ended => return false,
+                Mode::Flat => remaining -= 1,
+                Mode::Break => return true,
+            },
+            Doc::List(docs) | Doc::Group(docs) | Doc::Indent(docs) => {
+                commands.extend(docs.iter().rev().map(|doc| (mode, doc)));
+            }
+            Doc::OwnLineComment(_) => return mode == Mode::Break,
+            Doc::TrailingComment(_) => line_ended = true,
+        }
+
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::Write;
+
+use crate::{
+    optimizer::Optimizations,
+    output::SharedBuffer,
+    vm::{InterpretError, Vm},
+};
+
+/// The directory of golden tests that is run when no other is given.
+pub const DEFAULT_DIR: &str = "tests/lox";
+
+/// Runs Lox scripts that state what they should do in comments, in the style of the
+/// craftinginterpreters test suite:
+///
+///   1 + 2 // expect: 3                            A line of program output.
+///   -"a" // expect runtime error: Operand ...     A runtime error raised on this line.
+///   (1 // [line 2] Error at end: Expect ...       A compile error reported on line 2.
+///   ) // Error at ')': Expect expression.         A compile error reported on this line.
+///
+/// Each is turned into the line it expects in a transcript of the run: program output first,
+/// then errors, in the order they are written.
+pub struct GoldenTest {}
+
+impl GoldenTest {
+    pub fn expected(source: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        for (n, line) in source.lines().enumerate() {
+            let line_number = n + 1;
+
+            if let Some((_, expect)) = line.split_once("// expect: ") {
+                output.push(expect.to_string());
+            } else if let Some((_, message)) = line.split_once("// expect runtime error: ") {
+                errors.push(format!("[line {}] Error: {}", line_number, message));
+            } else if let Some((_, error)) = line.split_once("// [line ") {
+                errors.push(format!("[line {}", error));
+            } else if let Some((_, error)) = line.split_once("// Error") {
+                errors.push(format!("[line {}] Error{}", line_number, error));
+            }
+        }
+
+        output.extend(errors);
+        output
+    }
+
+    /// Runs source on a fresh VM compiling it the given way, and returns the transcript of the
+    /// run.
+    pub fn actual(source: &str, optimizations: Optimizations, typecheck: bool) -> Vec<String> {
+        let output = SharedBuffer::default();
+        let mut errors = SharedBuffer::default();
+        let mut vm = Vm::builder()
+            .output(Box::new(output.clone()))
+            .error_output(Box::new(errors.clone()))
+            .optimizations(optimizations)
+            .typecheck(typecheck)
+            .build();
+
+        // NOTE: Compile and runtime errors have already been written to the error output, which
+        //       is where the transcript takes them from. Bytecode the VM rejects isn't.
+        if let Err(error @ InterpretError::InvalidBytecode(_)) = vm.interpret(source) {
+            writeln!(errors, "{}", error).expect("Could not write to buffer");
+        }
+
+        let mut transcript = Vec::new();
+        for buffer in [output, errors] {
+            let text = String::from_utf8_lossy(&buffer.take()).into_owned();
+            transcript.extend(text.lines().map(|line| line.to_string()));
+        }
+
+        transcript
+    }
+
+    /// A line by line diff of two transcripts, with `-` before lines that were expected but
+    /// missing and `+` before lines that were not expected.
+    pub fn diff(expected: &[String], actual: &[String]) -> Vec<String> {
+        // The length of the longest common subsequence of every pair of suffixes.
+        let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+        for i in (0..expected.len()).rev() {
+            for j in (0..actual.len()).rev() {
+                lengths[i][j] = match expected[i] == actual[j] {
+                    true => lengths[i + 1][j + 1] + 1,
+                    false => lengths[i + 1][j].max(lengths[i][j + 1]),
+                };
+            }
+        }
+
+        let mut diff = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < expected.len() || j < actual.len() {
+            if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+                diff.push(format!("  {}", expected[i]));
+                i += 1;
+                j += 1;
+            } else if j == actual.len()
+                || (i < expected.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+            {
+                diff.push(format!("- {}", expected[i]));
+                i += 1;
+            } else {
+                diff.push(format!("+ {}", actual[j]));
+                j += 1;
+            }
+        }
+
+        diff
+    }
+}
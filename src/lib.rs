@@ -0,0 +1,77 @@
+//! A bytecode virtual machine for Lox, following the second half of Crafting Interpreters.
+//!
+//! Embedders need only what is exported here: build a `Vm` with `Vm::builder`, hand it source
+//! with `interpret` or `evaluate`, and move values across with `From` and `TryFrom`. The public
+//! modules are the command line tools the binary is built from, and may change between versions.
+
+use std::{
+    error::Error,
+    io::{Read, Write},
+};
+
+use assembler::Assembler;
+use serializer::Serializer;
+
+pub mod assembler;
+pub mod bench;
+pub mod dap;
+pub mod debugger;
+pub mod formatter;
+pub mod golden;
+pub mod linter;
+pub mod lsp;
+pub mod repl;
+pub mod serializer;
+
+pub(crate) mod ast;
+pub(crate) mod chunk;
+pub(crate) mod codegen;
+pub(crate) mod compiler;
+pub(crate) mod dissasembler;
+pub(crate) mod opcode;
+pub(crate) mod optimizer;
+pub(crate) mod output;
+pub(crate) mod scanner;
+pub(crate) mod transport;
+pub(crate) mod typecheck;
+pub(crate) mod value;
+pub(crate) mod verifier;
+pub(crate) mod vm;
+
+pub use chunk::Chunk;
+pub use compiler::Compiler;
+pub use opcode::Opcode;
+pub use optimizer::Optimizations;
+pub use output::SharedBuffer;
+pub use value::{Value, ValueTypeError};
+pub use verifier::VerifyError;
+pub use vm::{ExecutionState, InterpretError, Vm, VmBuilder};
+
+/// Loads a chunk from Lox source, .loxasm or .loxb, depending on the extension. The Lox source
+/// is handed back too, as it's the only one of the three that source lines refer to. Lox source
+/// is type checked first with `typecheck`, and compile errors are reported to `errors`.
+pub fn load_chunk(
+    path: &str,
+    optimizations: Optimizations,
+    typecheck: bool,
+    errors: &mut dyn Write,
+) -> Result<(Chunk, Option<String>), Box<dyn Error>> {
+    if path.ends_with(".loxb") {
+        let bytes = std::fs::read(path)?;
+        let chunk = Serializer::deserialize(&bytes)?;
+        return Ok((chunk, None));
+    }
+
+    if path.ends_with(".loxasm") {
+        let contents = std::fs::read_to_string(path)?;
+        let chunk = Assembler::assemble(&contents)?;
+        return Ok((chunk, None));
+    }
+
+    let mut file = std::fs::File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let chunk = compiler::compile(&contents, optimizations, typecheck, errors)?;
+    Ok((chunk, Some(contents)))
+}
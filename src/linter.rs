@@ -0,0 +1,260 @@
+use std::{collections::HashSet, error::Error, fmt::Display};
+
+use crate::{
+    ast::{AstParser, Expr, ExprKind},
+    scanner::{Scanner, TokenType},
+};
+
+// Each lint points at the source it is about with one of these.
+pub use crate::ast::Span;
+
+/// The config file that is read from the current directory when no other is given.
+pub const DEFAULT_CONFIG: &str = ".loxlint";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    ConstantComparison,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::ConstantComparison => "constant-comparison",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        match name {
+            "constant-comparison" => Some(Rule::ConstantComparison),
+            _ => None,
+        }
+    }
+}
+
+pub struct Lint {
+    pub rule: Rule,
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum LintError {
+    CompileError,
+    InvalidConfig { line: usize, message: String },
+}
+
+impl Display for LintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintError::CompileError => write!(f, "The source does not compile"),
+            LintError::InvalidConfig { line, message } => {
+                write!(f, "[line {}] Error in lint config: {}", line, message)
+            }
+        }
+    }
+}
+
+impl Error for LintError {}
+
+/// Which rules are turned off. Every rule is on unless the config says otherwise.
+#[derive(Default)]
+pub struct LintConfig {
+    disabled: HashSet<Rule>,
+}
+
+impl LintConfig {
+    /// Parses a config file made of `<rule> = on` and `<rule> = off` lines. Anything after a
+    /// `#` is a comment.
+    pub fn parse(contents: &str) -> Result<LintConfig, LintError> {
+        let mut config = LintConfig::default();
+
+        for (n, line) in contents.lines().enumerate() {
+            let error = |message: String| LintError::InvalidConfig {
+                line: n + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, setting) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("Expected '<rule> = on|off', found '{}'", line)))?;
+            let rule = Rule::from_name(name.trim())
+                .ok_or_else(|| error(format!("Unknown rule '{}'", name.trim())))?;
+
+            match setting.trim() {
+                "on" => config.disabled.remove(&rule),
+                "off" => config.disabled.insert(rule),
+                setting => {
+                    return Err(error(format!(
+                        "Expected 'on' or 'off', found '{}'",
+                        setting
+                    )))
+                }
+            };
+        }
+
+        Ok(config)
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+pub struct Linter<'src> {
+    source: &'src str,
+    config: &'src LintConfig,
+    lints: Vec<Lint>,
+}
+
+impl<'src> Linter<'src> {
+    /// Checks source that compiles against every enabled rule. A `// lox-allow(rule)` comment
+    /// silences that rule on its own line and the line after it.
+    pub fn lint(source: &'src str, config: &'src LintConfig) -> Result<Vec<Lint>, LintError> {
+        let script = AstParser::new(source)
+            .parse()
+            .map_err(|_| LintError::CompileError)?;
+
+        let mut linter = Linter {
+            source,
+            config,
+            lints: Vec::new(),
+        };
+        linter.visit(&script.body);
+
+        let allowed = linter.allowed();
+        let mut lints = linter.lints;
+        lints.retain(|lint| !allowed.contains(&(lint.rule, lint.span.line)));
+        Ok(lints)
+    }
+
+    // The (rule, line) pairs that inline comments allow.
+    fn allowed(&self) -> HashSet<(Rule, usize)> {
+        // NOTE: The source has already parsed, so the scanner can be run to the end.
+        let mut scanner = Scanner::new(self.source);
+        while let Ok(token) = scanner.scan_token() {
+            if token.token_type == TokenType::Eof {
+                break;
+            }
+        }
+
+        let mut allowed = HashSet::new();
+        for comment in scanner.comments() {
+            let text = comment.lexeme(self.source);
+            let rules = text
+                .split_once("lox-allow(")
+                .and_then(|(_, rest)| rest.split_once(')'))
+                .map(|(rules, _)| rules);
+
+            for name in rules.into_iter().flat_map(|rules| rules.split(',')) {
+                if let Some(rule) = Rule::from_name(name.trim()) {
+                    allowed.insert((rule, comment.line));
+                    allowed.insert((rule, comment.line + 1));
+                }
+            }
+        }
+
+        allowed
+    }
+
+    fn visit(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Invalid => {}
+            ExprKind::Grouping(inner) => self.visit(inner),
+            ExprKind::Unary { operand, .. } => self.visit(operand),
+            ExprKind::Binary {
+                left,
+                operator,
+                operator_span,
+                right,
+            } => {
+                if operator.is_comparison() {
+                    self.check_comparison(left, *operator_span, right);
+                }
+                self.visit(left);
+                self.visit(right);
+            }
+        }
+    }
+
+    fn check_comparison(&mut self, left: &Expr, operator_span: Span, right: &Expr) {
+        let (left, right) = (ungroup(left), ungroup(right));
+        let lexeme = operator_span.lexeme(self.source);
+
+        // NOTE: Neither message says which way the comparison goes, as `0/0 == 0/0` is false
+        //       and strings never compare equal at runtime.
+        let message = if same(left, right) {
+            format!(
+                "Both sides of '{}' are the same, so it always has the same result.",
+                lexeme
+            )
+        } else if matches!(
+            (&left.kind, &right.kind),
+            (ExprKind::Literal(_), ExprKind::Literal(_))
+        ) {
+            format!(
+                "'{}' compares two literals, so it always has the same result.",
+                lexeme
+            )
+        } else {
+            return;
+        };
+
+        self.report(Rule::ConstantComparison, operator_span, message);
+    }
+
+    fn report(&mut self, rule: Rule, span: Span, message: String) {
+        if self.config.is_enabled(rule) {
+            self.lints.push(Lint {
+                rule,
+                span,
+                message,
+            });
+        }
+    }
+}
+
+fn ungroup(expr: &Expr) -> &Expr {
+    match &expr.kind {
+        ExprKind::Grouping(inner) => ungroup(inner),
+        _ => expr,
+    }
+}
+
+// Whether two expressions are the same apart from where they are and any parentheses.
+fn same(a: &Expr, b: &Expr) -> bool {
+    match (&ungroup(a).kind, &ungroup(b).kind) {
+        (ExprKind::Literal(a), ExprKind::Literal(b)) => a == b,
+        (
+            ExprKind::Unary {
+                operator: a,
+                operand: a_operand,
+                ..
+            },
+            ExprKind::Unary {
+                operator: b,
+                operand: b_operand,
+                ..
+            },
+        ) => a == b && same(a_operand, b_operand),
+        (
+            ExprKind::Binary {
+                left: a_left,
+                operator: a,
+                right: a_right,
+                ..
+            },
+            ExprKind::Binary {
+                left: b_left,
+                operator: b,
+                right: b_right,
+                ..
+            },
+        ) => a == b && same(a_left, b_left) && same(a_right, b_right),
+        _ => false,
+    }
+}
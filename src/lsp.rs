@@ -0,0 +1,372 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{BufRead, Write},
+};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    ast::AstParser,
+    compiler::Diagnostic,
+    scanner::{Scanner, Token, TokenCategory, TokenType, KEYWORDS},
+    transport::{read_message, write_message},
+    typecheck::TypeChecker,
+};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+const PARSE_ERROR: i64 = -32700;
+
+const TEXT_DOCUMENT_SYNC_FULL: u64 = 1;
+const SEVERITY_ERROR: u64 = 1;
+const COMPLETION_KIND_VARIABLE: u64 = 6;
+const COMPLETION_KIND_KEYWORD: u64 = 14;
+
+// The order here gives each token type its index in the semantic tokens legend.
+const SEMANTIC_TOKEN_TYPES: [&str; 5] = ["keyword", "string", "number", "operator", "variable"];
+
+/// A Language Server Protocol server, speaking JSON-RPC over the given reader and writer. Every
+/// document is rescanned and recompiled in full whenever it changes.
+pub struct LspServer<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl<R: BufRead, W: Write> LspServer<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        LspServer {
+            input,
+            output,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Serves requests until `exit` or the end of input, and returns the status to exit with,
+    /// which is 1 when the client sends `exit` without asking the server to `shutdown` first.
+    pub fn run(&mut self) -> std::io::Result<i32> {
+        while let Some(message) = read_message(&mut self.input)? {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    self.respond_error(&Json::Null, PARSE_ERROR, &error.to_string())?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+
+            if method == "exit" {
+                return Ok(match self.shutdown {
+                    true => 0,
+                    false => 1,
+                });
+            }
+
+            match message.get("id") {
+                Some(id) => self.handle_request(id, method, params)?,
+                None => self.handle_notification(method, params)?,
+            }
+        }
+
+        Ok(0)
+    }
+
+    fn handle_request(&mut self, id: &Json, method: &str, params: &Json) -> std::io::Result<()> {
+        if self.shutdown {
+            return self.respond_error(id, INVALID_REQUEST, "The server is shutting down");
+        }
+
+        match method {
+            "initialize" => {
+                let result = json!({
+                    "capabilities": {
+                        "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+                        "semanticTokensProvider": {
+                            "legend": {
+                                "tokenTypes": SEMANTIC_TOKEN_TYPES,
+                                "tokenModifiers": [],
+                            },
+                            "full": true,
+                        },
+                        "completionProvider": {},
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": { "name": "lox" },
+                });
+                self.respond(id, result)
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                self.respond(id, Json::Null)
+            }
+            "textDocument/semanticTokens/full" => {
+                let data = self
+                    .document(params)
+                    .map(semantic_tokens)
+                    .unwrap_or_default();
+                self.respond(id, json!({ "data": data }))
+            }
+            "textDocument/completion" => {
+                let items = self.document(params).map(completions).unwrap_or_default();
+                self.respond(id, json!(items))
+            }
+            "textDocument/hover" => {
+                let hover = self
+                    .document(params)
+                    .and_then(|source| hover(source, &params["position"]))
+                    .unwrap_or_default();
+                self.respond(id, hover)
+            }
+            // NOTE: There are no declarations yet, so there is never anything to go to, refer to
+            //       or list. See ROADMAP.md.
+            "textDocument/definition" => self.respond(id, Json::Null),
+            "textDocument/references" | "textDocument/documentSymbol" => {
+                self.respond(id, json!([]))
+            }
+            _ => {
+                let message = format!("Unsupported request '{}'", method);
+                self.respond_error(id, METHOD_NOT_FOUND, &message)
+            }
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> std::io::Result<()> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                // NOTE: Only full document sync is offered, so the last change is the whole text.
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn document(&self, params: &Json) -> Option<&str> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        self.documents.get(uri).map(|source| source.as_str())
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> std::io::Result<()> {
+        let diagnostics: Vec<Json> = match self.documents.get(uri) {
+            Some(source) => {
+                let mut parser = AstParser::new(source);
+                let _ = parser.parse();
+                parser
+                    .diagnostics()
+                    .iter()
+                    .map(|diagnostic| to_lsp_diagnostic(source, diagnostic))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    fn respond(&mut self, id: &Json, result: Json) -> std::io::Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond_error(&mut self, id: &Json, code: i64, message: &str) -> std::io::Result<()> {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        });
+        write_message(&mut self.output, &message)
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> std::io::Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.output, &message)
+    }
+}
+
+fn to_lsp_diagnostic(source: &str, diagnostic: &Diagnostic) -> Json {
+    let range = match diagnostic.token {
+        Some(token) => json!({
+            "start": position(source, token.start),
+            "end": position(source, token.start + token.length),
+        }),
+        // NOTE: Scanner errors only know their line, so they cover the whole of it.
+        None => {
+            let line = diagnostic.line.saturating_sub(1);
+            let length = source.lines().nth(line).map_or(0, utf16_len);
+            json!({
+                "start": { "line": line, "character": 0 },
+                "end": { "line": line, "character": length },
+            })
+        }
+    };
+
+    json!({
+        "range": range,
+        "severity": SEVERITY_ERROR,
+        "source": "lox",
+        "message": diagnostic.message,
+    })
+}
+
+// Token offsets count bytes, while LSP positions are a zero based line and a UTF-16 column.
+fn position(source: &str, offset: usize) -> Json {
+    let mut line = 0;
+    let mut character = 0;
+    for c in source[..offset.min(source.len())].chars() {
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16();
+        }
+    }
+    json!({ "line": line, "character": character })
+}
+
+// The byte offset of an LSP position, or None if it is past the end of its line.
+fn offset(source: &str, position: &Json) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+
+    let mut line_start = 0;
+    for _ in 0..line {
+        line_start += source[line_start..].find('\n')? + 1;
+    }
+
+    let mut column = 0;
+    for (index, c) in source[line_start..].char_indices() {
+        if column >= character || c == '\n' {
+            return (column == character).then_some(line_start + index);
+        }
+        column += c.len_utf16();
+    }
+    (column == character).then_some(source.len())
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+fn tokens(source: &str) -> Vec<Token> {
+    let mut scanner = Scanner::new(source);
+    let mut tokens = Vec::new();
+
+    loop {
+        // NOTE: Characters the scanner rejects are skipped, they are already reported as
+        //       diagnostics by the compiler.
+        if let Ok(token) = scanner.scan_token() {
+            if token.token_type == TokenType::Eof {
+                return tokens;
+            }
+            tokens.push(token);
+        }
+    }
+}
+
+fn semantic_token_type(token_type: TokenType) -> Option<usize> {
+    let name = match token_type.category()? {
+        TokenCategory::Keyword => "keyword",
+        TokenCategory::String => "string",
+        TokenCategory::Number => "number",
+        TokenCategory::Operator => "operator",
+        TokenCategory::Identifier => "variable",
+    };
+    SEMANTIC_TOKEN_TYPES.iter().position(|t| *t == name)
+}
+
+// Encodes tokens as the relative (line, start, length, type, modifiers) runs that LSP expects. A
+// string that spans lines is only highlighted on its first line.
+fn semantic_tokens(source: &str) -> Vec<usize> {
+    let mut data = Vec::new();
+    let mut previous_line = 0;
+    let mut previous_start = 0;
+
+    for token in tokens(source) {
+        let token_type = match semantic_token_type(token.token_type) {
+            Some(token_type) => token_type,
+            None => continue,
+        };
+
+        let start = position(source, token.start);
+        let line = start["line"].as_u64().unwrap() as usize;
+        let character = start["character"].as_u64().unwrap() as usize;
+        let lexeme = token.lexeme(source);
+        let length = utf16_len(lexeme.lines().next().unwrap_or_default());
+
+        let delta_start = match line == previous_line {
+            true => character - previous_start,
+            false => character,
+        };
+        data.extend_from_slice(&[line - previous_line, delta_start, length, token_type, 0]);
+
+        previous_line = line;
+        previous_start = character;
+    }
+
+    data
+}
+
+// The type of the innermost expression at a position, as the type checker infers it.
+fn hover(source: &str, at: &Json) -> Option<Json> {
+    let offset = offset(source, at)?;
+    let script = AstParser::new(source).parse().ok()?;
+
+    let (span, expr_type) = TypeChecker::types(&script)
+        .into_iter()
+        .filter(|(span, _)| span.start <= offset && offset < span.start + span.length)
+        .min_by_key(|(span, _)| span.length)?;
+
+    Some(json!({
+        "contents": { "kind": "plaintext", "value": expr_type.to_string() },
+        "range": {
+            "start": position(source, span.start),
+            "end": position(source, span.start + span.length),
+        },
+    }))
+}
+
+fn completions(source: &str) -> Vec<Json> {
+    let identifiers: BTreeSet<&str> = tokens(source)
+        .iter()
+        .filter(|token| token.token_type == TokenType::Identifier)
+        .map(|token| token.lexeme(source))
+        .collect();
+
+    let keywords = KEYWORDS
+        .iter()
+        .map(|keyword| json!({ "label": keyword, "kind": COMPLETION_KIND_KEYWORD }));
+    let identifiers = identifiers
+        .into_iter()
+        .map(|identifier| json!({ "label": identifier, "kind": COMPLETION_KIND_VARIABLE }));
+
+    keywords.chain(identifiers).collect()
+}
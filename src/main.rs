@@ -1,59 +1,640 @@
-use std::io::{Read, Write};
+use std::{error::Error, io::Read, path::PathBuf};
 
-use clap::Parser;
-use opcode::Opcode;
+use clap::{Parser, Subcommand};
+use crafting_interpreters_vm::{
+    assembler::Assembler,
+    bench::{self, Bench, BenchResult},
+    dap::DapServer,
+    debugger::Debugger,
+    formatter::Formatter,
+    golden::{self, GoldenTest},
+    linter::{self, LintConfig, Linter},
+    load_chunk,
+    lsp::LspServer,
+    repl::Repl,
+    serializer::Serializer,
+    Chunk, Compiler, InterpretError, Optimizations, Vm,
+};
 
-use crate::vm::Vm;
-
-mod chunk;
-mod compiler;
-mod dissasembler;
-mod opcode;
-mod scanner;
-mod value;
-mod vm;
+// Exit codes from sysexits.h, as used by clox.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
 
 #[derive(Parser)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// A Lox source, .loxasm or .loxb file to run, or - to read Lox source from stdin
     path: Option<String>,
+    /// Run this Lox source instead of a file
+    #[arg(
+        short = 'e',
+        long = "eval",
+        value_name = "CODE",
+        allow_hyphen_values = true,
+        conflicts_with = "path"
+    )]
+    eval: Option<String>,
+    /// Write the `DEBUG` execution trace to this file instead of stderr
+    #[arg(long)]
+    trace_output: Option<String>,
+    /// Run the file under the interactive debugger
+    #[arg(long)]
+    debug: bool,
+    /// Don't optimize when compiling Lox source
+    #[arg(long, global = true)]
+    no_opt: bool,
+    /// Don't fold constants, but still run the other optimizations
+    #[arg(long, global = true)]
+    no_fold: bool,
+    /// Don't emit superinstructions, but still run the other optimizations
+    #[arg(long, global = true)]
+    no_superinstructions: bool,
+    /// Check the types in Lox source before running or compiling it, and report operations
+    /// that are bound to fail as compile errors
+    #[arg(long, global = true)]
+    typecheck: bool,
+}
+
+impl Cli {
+    fn optimizations(&self) -> Optimizations {
+        if self.no_opt {
+            return Optimizations::none();
+        }
+
+        Optimizations {
+            fold_constants: !self.no_fold,
+            superinstructions: !self.no_superinstructions,
+            ..Optimizations::all()
+        }
+    }
+
+    // The first of the flags that say how to compile Lox source which was given, if any.
+    fn compile_flag(&self) -> Option<&'static str> {
+        [
+            ("--no-opt", self.no_opt),
+            ("--no-fold", self.no_fold),
+            ("--no-superinstructions", self.no_superinstructions),
+            ("--typecheck", self.typecheck),
+        ]
+        .into_iter()
+        .find_map(|(flag, given)| given.then_some(flag))
+    }
+
+    fn program(&self) -> Option<Program> {
+        match (&self.eval, &self.path) {
+            (Some(source), _) => Some(Program::Eval(source.clone())),
+            (None, Some(path)) if path == "-" => Some(Program::Stdin),
+            (None, Some(path)) => Some(Program::File(path.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+// Where the program to run comes from.
+enum Program {
+    File(String),
+    Stdin,
+    Eval(String),
+}
+
+impl Program {
+    fn name(&self) -> &str {
+        match self {
+            Program::File(path) => path,
+            Program::Stdin => "<stdin>",
+            Program::Eval(_) => "<eval>",
+        }
+    }
+
+    // Lox source is compiled by the VM, so that it is checked and compiled the way the VM is set
+    // up to.
+    fn load(&self, vm: &mut Vm) -> Result<(Chunk, Option<String>), Box<dyn Error>> {
+        let source = match self {
+            Program::File(path) if path.ends_with(".loxb") || path.ends_with(".loxasm") => {
+                return load_chunk(
+                    path,
+                    vm.optimizations(),
+                    vm.typecheck(),
+                    &mut std::io::stderr(),
+                )
+            }
+            Program::File(path) => std::fs::read_to_string(path)?,
+            Program::Stdin => {
+                let mut source = String::new();
+                std::io::stdin().read_to_string(&mut source)?;
+                source
+            }
+            Program::Eval(source) => source.clone(),
+        };
+
+        let chunk = vm.compile(&source)?;
+        Ok((chunk, Some(source)))
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Time Lox programs, by default every one in the bench directory
+    Bench {
+        paths: Vec<String>,
+        /// How many times to run each program
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+        runs: u64,
+        /// Write the results to this file as JSON
+        #[arg(long)]
+        save: Option<String>,
+        /// Compare the results against ones saved earlier with --save
+        #[arg(long)]
+        baseline: Option<String>,
+    },
+    /// Compile a Lox source or .loxasm file to a bytecode file
+    Compile {
+        path: String,
+        #[arg(short, long)]
+        output: String,
+        /// Compile Lox source with the single-pass compiler, which never folds constants or checks
+        /// types
+        #[arg(long)]
+        single_pass: bool,
+    },
+    /// Run a Debug Adapter Protocol server over stdin and stdout
+    Dap,
+    /// Reformat Lox source files in place
+    Fmt {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Don't write anything, exit with an error if any file would change
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check Lox source files for likely mistakes
+    Lint {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Read rule settings from this file instead of .loxlint
+        #[arg(long)]
+        config: Option<String>,
+    },
+    /// Run a Language Server Protocol server over stdin and stdout
+    Lsp,
+    /// Run Lox scripts annotated with their expected output, by default every one in tests/lox
+    Test {
+        paths: Vec<String>,
+        /// Only run scripts whose path contains this
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+impl Command {
+    // The name of a subcommand that never compiles Lox source to run, for which the flags that say
+    // how to compile it mean nothing.
+    fn ignores_compile_flags(&self) -> Option<&'static str> {
+        match self {
+            Command::Dap => Some("dap"),
+            Command::Fmt { .. } => Some("fmt"),
+            Command::Lint { .. } => Some("lint"),
+            Command::Lsp => Some("lsp"),
+            Command::Bench { .. } | Command::Compile { .. } | Command::Test { .. } => None,
+        }
+    }
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::try_parse().unwrap_or_else(|error| {
+        // NOTE: Asking for --help or --version ends up here too, and isn't an error.
+        let _ = error.print();
+        std::process::exit(match error.use_stderr() {
+            true => EX_USAGE,
+            false => 0,
+        })
+    });
+    if let (Some(flag), Some(command)) = (
+        cli.compile_flag(),
+        cli.command
+            .as_ref()
+            .and_then(Command::ignores_compile_flags),
+    ) {
+        exit_with_usage_error(&format!("{} can't be used with {}", flag, command));
+    }
+    let optimizations = cli.optimizations();
+    let program = cli.program();
+    let typecheck = cli.typecheck;
 
     match cli {
-        Cli { path: Some(path) } => {
-            run_file(path);
+        Cli {
+            command:
+                Some(Command::Bench {
+                    paths,
+                    runs,
+                    save,
+                    baseline,
+                }),
+            ..
+        } => {
+            bench_files(paths, runs, save, baseline, optimizations, typecheck);
+        }
+        Cli {
+            command:
+                Some(Command::Compile {
+                    path,
+                    output,
+                    single_pass,
+                }),
+            ..
+        } => {
+            compile_file(path, output, single_pass, optimizations, typecheck);
+        }
+        Cli {
+            command: Some(Command::Dap),
+            ..
+        } => {
+            let mut server = DapServer::new(std::io::stdin().lock(), std::io::stdout());
+            if let Err(error) = server.run() {
+                exit_with_error("Could not run debug adapter", &error);
+            }
+        }
+        Cli {
+            command: Some(Command::Fmt { paths, check }),
+            ..
+        } => {
+            format_files(paths, check);
+        }
+        Cli {
+            command: Some(Command::Lint { paths, config }),
+            ..
+        } => {
+            lint_files(paths, config);
+        }
+        Cli {
+            command: Some(Command::Lsp),
+            ..
+        } => {
+            let mut server = LspServer::new(std::io::stdin().lock(), std::io::stdout());
+            match server.run() {
+                Ok(code) => std::process::exit(code),
+                Err(error) => exit_with_error("Could not run language server", &error),
+            }
+        }
+        Cli {
+            command: Some(Command::Test { paths, filter }),
+            ..
+        } => {
+            test_files(paths, filter, optimizations, typecheck);
+        }
+        Cli {
+            trace_output,
+            debug,
+            ..
+        } => {
+            let vm = new_vm(trace_output, optimizations, typecheck);
+            match (program, debug) {
+                (Some(program), true) => debug_program(program, vm),
+                (Some(program), false) => run_program(program, vm),
+                (None, _) => repl(vm),
+            }
+        }
+    }
+}
+
+/// Reports an error that stops the CLI and exits with the sysexits code for its kind. Compile
+/// and runtime errors have already been reported by the compiler or VM, so they only exit.
+fn exit_with_error(context: &str, error: &(dyn Error + 'static)) -> ! {
+    let code = match error.downcast_ref::<InterpretError>() {
+        Some(InterpretError::CompileError) => std::process::exit(EX_DATAERR),
+        Some(InterpretError::RuntimeError(_)) => std::process::exit(EX_SOFTWARE),
+        Some(InterpretError::InvalidBytecode(_)) => EX_DATAERR,
+        None if error.is::<std::io::Error>() => EX_IOERR,
+        None => EX_DATAERR,
+    };
+
+    eprintln!("{}: {}", context, error);
+    std::process::exit(code)
+}
+
+fn exit_with_usage_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(EX_USAGE)
+}
+
+fn new_vm(trace_output: Option<String>, optimizations: Optimizations, typecheck: bool) -> Vm {
+    let mut builder = Vm::builder()
+        .optimizations(optimizations)
+        .typecheck(typecheck);
+
+    if let Some(path) = trace_output {
+        let file = std::fs::File::create(&path).unwrap_or_else(|error| {
+            exit_with_error(&format!("Could not create trace output '{}'", path), &error)
+        });
+        builder = builder.trace_output(Box::new(file));
+    }
+
+    builder.build()
+}
+
+fn repl(vm: Vm) {
+    let mut repl = Repl::new(vm);
+    if let Err(error) = repl.run() {
+        exit_with_error("Could not run REPL", &error);
+    }
+}
+
+fn run_program(program: Program, mut vm: Vm) {
+    let (chunk, _) = program.load(&mut vm).unwrap_or_else(|error| {
+        exit_with_error(&format!("Could not load '{}'", program.name()), &*error)
+    });
+
+    if let Err(error) = vm.interpret_chunk(chunk) {
+        exit_with_error(&format!("Could not run '{}'", program.name()), &error);
+    }
+}
+
+fn debug_program(program: Program, mut vm: Vm) {
+    if let Program::Stdin = program {
+        exit_with_usage_error(
+            "Can't debug a program read from stdin, which debugger commands are read from",
+        );
+    }
+
+    // NOTE: Constants are never folded while debugging, so that every operation in the source can
+    //       still be stepped through.
+    vm.set_optimizations(Optimizations::none());
+    let (chunk, source) = program.load(&mut vm).unwrap_or_else(|error| {
+        exit_with_error(&format!("Could not load '{}'", program.name()), &*error)
+    });
+    if let Err(error) = vm.load(chunk) {
+        exit_with_error(&format!("Could not load '{}'", program.name()), &error);
+    }
+
+    let mut debugger = Debugger::new(vm, source.as_deref());
+    if let Err(error) = debugger.run(std::io::stdin().lock(), std::io::stdout()) {
+        exit_with_error("Could not run debugger", &error);
+    }
+    if debugger.failed() {
+        std::process::exit(EX_SOFTWARE);
+    }
+}
+
+fn compile_file(
+    path: String,
+    output: String,
+    single_pass: bool,
+    optimizations: Optimizations,
+    typecheck: bool,
+) {
+    let contents = std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| exit_with_error(&format!("Could not read '{}'", path), &error));
+
+    if typecheck && single_pass {
+        exit_with_usage_error("--typecheck checks the AST, which --single-pass never builds");
+    }
+
+    let chunk: Result<Chunk, Box<dyn Error>> = if path.ends_with(".loxasm") {
+        Assembler::assemble(&contents).map_err(Box::from)
+    } else if single_pass {
+        let mut compiler = Compiler::new(&contents);
+        compiler.compile(&mut std::io::stderr()).map_err(Box::from)
+    } else {
+        // NOTE: The listing is dumped below for every compiler, not by the VM.
+        let mut vm = Vm::builder()
+            .optimizations(optimizations)
+            .typecheck(typecheck)
+            .dump(false)
+            .build();
+        vm.compile(&contents).map_err(Box::from)
+    };
+    let chunk = chunk
+        .unwrap_or_else(|error| exit_with_error(&format!("Could not compile '{}'", path), &*error));
+    if std::env::var("DUMP").is_ok() {
+        eprintln!("{:?}", chunk);
+    }
+
+    if let Err(error) = std::fs::write(&output, Serializer::serialize(&chunk)) {
+        exit_with_error(&format!("Could not write '{}'", output), &error);
+    }
+}
+
+fn bench_files(
+    paths: Vec<String>,
+    runs: u64,
+    save: Option<String>,
+    baseline: Option<String>,
+    optimizations: Optimizations,
+    typecheck: bool,
+) {
+    let baseline = baseline.map(|path| {
+        let contents = std::fs::read_to_string(&path).unwrap_or_else(|error| {
+            exit_with_error(&format!("Could not read baseline '{}'", path), &error)
+        });
+        Bench::parse_baseline(&contents).unwrap_or_else(|error| {
+            exit_with_error(&format!("Could not parse baseline '{}'", path), &error)
+        })
+    });
+
+    // NOTE: The programs are made of nothing but literals, so folding would leave each one as a
+    //       single constant with nothing to measure.
+    let optimizations = Optimizations {
+        fold_constants: false,
+        ..optimizations
+    };
+
+    let mut results: Vec<BenchResult> = Vec::new();
+    for file in lox_files(paths, bench::DEFAULT_DIR) {
+        let path = file.to_string_lossy();
+        let name = file.file_stem().unwrap_or_default().to_string_lossy();
+
+        let (chunk, _) = load_chunk(&path, optimizations, typecheck, &mut std::io::stderr())
+            .unwrap_or_else(|error| {
+                exit_with_error(&format!("Could not load '{}'", path), &*error)
+            });
+        let result = Bench::run(&name, &chunk, runs as usize)
+            .unwrap_or_else(|error| exit_with_error(&format!("Could not run '{}'", path), &error));
+
+        let mut line = format!(
+            "{:<16} {:>10.2}us ± {:>8.2}us {:>10.2}M instructions/s",
+            result.name,
+            result.mean * 1e6,
+            result.stddev * 1e6,
+            result.instructions_per_second() / 1e6
+        );
+        if let Some(mean) = baseline.as_ref().and_then(|b| b.get(&result.name)) {
+            let change = (result.mean - mean) / mean * 100.0;
+            line.push_str(&format!(" {:>+8.1}% vs baseline", change));
+        }
+        println!("{}", line);
+
+        results.push(result);
+    }
+
+    if let Some(path) = save {
+        let json = serde_json::to_string_pretty(&Bench::to_json(&results))
+            .expect("Could not serialize results");
+        if let Err(error) = std::fs::write(&path, json) {
+            exit_with_error(&format!("Could not write results '{}'", path), &error);
+        }
+    }
+}
+
+fn test_files(
+    paths: Vec<String>,
+    filter: Option<String>,
+    optimizations: Optimizations,
+    typecheck: bool,
+) {
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for file in lox_files(paths, golden::DEFAULT_DIR) {
+        let path = file.to_string_lossy();
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !path.contains(filter.as_str()))
+        {
+            continue;
         }
-        Cli { path: None } => {
-            repl();
+
+        let source = std::fs::read_to_string(&file)
+            .unwrap_or_else(|error| exit_with_error(&format!("Could not read '{}'", path), &error));
+        let expected = GoldenTest::expected(&source);
+        let actual = GoldenTest::actual(&source, optimizations, typecheck);
+
+        if expected == actual {
+            passed += 1;
+            continue;
         }
+
+        failed += 1;
+        println!("FAIL {}", path);
+        for line in GoldenTest::diff(&expected, &actual) {
+            println!("    {}", line);
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// The files given, with any directories replaced by every .lox file under them, or every one
+// under the default directory when none are given.
+fn lox_files(paths: Vec<String>, default_dir: &str) -> Vec<PathBuf> {
+    let paths = match paths.is_empty() {
+        true => vec![default_dir.to_string()],
+        false => paths,
+    };
+
+    let mut files = Vec::new();
+    for path in paths {
+        add_lox_files(PathBuf::from(path), &mut files);
+    }
+
+    files
+}
+
+fn add_lox_files(path: PathBuf, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path);
+        return;
+    }
+
+    let read_error = |error: std::io::Error| -> ! {
+        exit_with_error(&format!("Could not read '{}'", path.display()), &error)
+    };
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(&path)
+        .unwrap_or_else(|error| read_error(error))
+        .map(|entry| entry.unwrap_or_else(|error| read_error(error)).path())
+        .filter(|path| path.is_dir() || path.extension().is_some_and(|e| e == "lox"))
+        .collect();
+    entries.sort();
+
+    for entry in entries {
+        add_lox_files(entry, files);
     }
 }
 
-fn repl() {
-    let mut vm = Vm::new();
+fn format_files(paths: Vec<String>, check: bool) {
+    let mut failed = false;
 
-    loop {
-        print!("> ");
-        std::io::stdout().flush().expect("Could not flush stdout");
+    for path in paths {
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|error| exit_with_error(&format!("Could not read '{}'", path), &error));
+
+        let formatted = match Formatter::format(&contents) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("Could not format '{}': {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == contents {
+            continue;
+        }
 
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("Could not read line");
+        if check {
+            println!("Would reformat '{}'", path);
+            failed = true;
+        } else {
+            if let Err(error) = std::fs::write(&path, formatted) {
+                exit_with_error(&format!("Could not write '{}'", path), &error);
+            }
+        }
+    }
 
-        vm.interpret(&input).expect("Could not interpret input");
+    if failed {
+        std::process::exit(1);
     }
 }
 
-fn run_file(path: String) {
-    let mut vm = Vm::new();
+fn lint_files(paths: Vec<String>, config: Option<String>) {
+    let config = match config {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(error) => {
+                exit_with_error(&format!("Could not read lint config '{}'", path), &error)
+            }
+        },
+        None => std::fs::read_to_string(linter::DEFAULT_CONFIG).ok(),
+    };
+    let config = match config {
+        Some(contents) => LintConfig::parse(&contents)
+            .unwrap_or_else(|error| exit_with_error("Could not parse lint config", &error)),
+        None => LintConfig::default(),
+    };
 
-    let mut file = std::fs::File::open(path).expect("Could not open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .expect("Could not read file");
+    let mut failed = false;
 
-    vm.interpret(&contents).expect("Could not interpret file");
+    for path in paths {
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|error| exit_with_error(&format!("Could not read '{}'", path), &error));
+
+        match Linter::lint(&contents, &config) {
+            Ok(lints) => {
+                for lint in &lints {
+                    println!(
+                        "{}: [line {}] Warning at '{}': {} ({})",
+                        path,
+                        lint.span.line,
+                        lint.span.lexeme(&contents),
+                        lint.message,
+                        lint.rule.name()
+                    );
+                }
+                failed |= !lints.is_empty();
+            }
+            Err(error) => {
+                eprintln!("Could not lint '{}': {}", path, error);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
 }
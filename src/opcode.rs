@@ -1,5 +1,5 @@
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Opcode {
     Return,
     Constant,
@@ -16,11 +16,16 @@ pub enum Opcode {
     Equal,
     Greater,
     Less,
+    NotEqual,
+    GreaterEqual,
+    LessEqual,
+    AddConstant,
+    ReturnNil,
 }
 
-impl From<u8> for Opcode {
-    fn from(byte: u8) -> Self {
-        match byte {
+impl Opcode {
+    pub const fn from_byte(byte: u8) -> Option<Opcode> {
+        let opcode = match byte {
             0 => Opcode::Return,
             1 => Opcode::Constant,
             2 => Opcode::ConstantLong,
@@ -36,7 +41,31 @@ impl From<u8> for Opcode {
             12 => Opcode::Equal,
             13 => Opcode::Greater,
             14 => Opcode::Less,
-            _ => panic!("Unknown opcode {}", byte),
+            15 => Opcode::NotEqual,
+            16 => Opcode::GreaterEqual,
+            17 => Opcode::LessEqual,
+            18 => Opcode::AddConstant,
+            19 => Opcode::ReturnNil,
+            _ => return None,
+        };
+        Some(opcode)
+    }
+
+    /// Number of operand bytes that follow the opcode in `Chunk::code`.
+    pub fn operand_len(&self) -> usize {
+        match self {
+            Opcode::Constant | Opcode::AddConstant => 1,
+            Opcode::ConstantLong => 4,
+            _ => 0,
+        }
+    }
+}
+
+impl From<u8> for Opcode {
+    fn from(byte: u8) -> Self {
+        match Opcode::from_byte(byte) {
+            Some(opcode) => opcode,
+            None => panic!("Unknown opcode {}", byte),
         }
     }
 }
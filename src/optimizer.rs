@@ -0,0 +1,245 @@
+use std::cmp::Ordering;
+
+use crate::{
+    ast::{BinaryOperator, Expr, ExprKind, Literal, Script, Span, UnaryOperator},
+    chunk::Chunk,
+    opcode::Opcode,
+    value::{Obj, Value, ValueKind},
+};
+
+// Instruction pairs that have a single instruction doing the same thing. The replacement keeps
+// the first instruction's operand.
+const PEEPHOLE_RULES: [(Opcode, Opcode, Opcode); 3] = [
+    (Opcode::Equal, Opcode::Not, Opcode::NotEqual),
+    (Opcode::Less, Opcode::Not, Opcode::GreaterEqual),
+    (Opcode::Greater, Opcode::Not, Opcode::LessEqual),
+];
+
+// The same, for pairs whose replacement is a superinstruction. These are only applied when
+// superinstructions are, and catch what the code generator leaves behind, such as `1 + (2)`
+// without folding.
+const SUPERINSTRUCTION_RULES: [(Opcode, Opcode, Opcode); 2] = [
+    (Opcode::Constant, Opcode::Add, Opcode::AddConstant),
+    (Opcode::Nil, Opcode::Return, Opcode::ReturnNil),
+];
+
+/// Which optimizations `compiler::compile` runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Optimizations {
+    pub fold_constants: bool,
+    pub peephole: bool,
+    pub superinstructions: bool,
+}
+
+impl Optimizations {
+    pub fn all() -> Self {
+        Optimizations {
+            fold_constants: true,
+            peephole: true,
+            superinstructions: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Optimizations {
+            fold_constants: false,
+            peephole: false,
+            superinstructions: false,
+        }
+    }
+}
+
+pub struct Optimizer {}
+
+impl Optimizer {
+    /// Replaces operations on literals with their result. Each operation is evaluated exactly as
+    /// `Vm::step` would, and any that would be a runtime error, such as `-"a"` or `1 + nil`, is
+    /// left alone so that it still fails when run.
+    pub fn fold_constants(script: Script) -> Script {
+        Script {
+            body: fold(script.body),
+            end: script.end,
+        }
+    }
+
+    /// Rewrites instruction sequences in a chunk into shorter ones, see `PEEPHOLE_RULES`, and
+    /// `SUPERINSTRUCTION_RULES` with `superinstructions`. Each rewritten instruction takes the
+    /// line of the one in the pair that does the work, which is where the runtime error for the
+    /// sequence would have been reported: the comparison before a `Not`, or the instruction
+    /// after a `Constant` or `Nil`.
+    ///
+    /// NOTE: There are no jumps yet, so nothing can land in the middle of a sequence and no
+    ///       offsets need patching once the code has moved.
+    pub fn peephole(chunk: Chunk, superinstructions: bool) -> Chunk {
+        let superinstruction_rules: &[_] = if superinstructions {
+            &SUPERINSTRUCTION_RULES
+        } else {
+            &[]
+        };
+
+        let mut optimized = Chunk::new(chunk.name.clone());
+        optimized.constants = chunk.constants.clone();
+
+        let mut offset = 0;
+        while offset < chunk.code.len() {
+            let opcode = Opcode::from(chunk.code[offset]);
+            let length = 1 + opcode.operand_len();
+            let line = chunk.line_for_instruction_n(offset + 1);
+
+            let next = chunk.code.get(offset + length).copied();
+            let rule = PEEPHOLE_RULES
+                .iter()
+                .chain(superinstruction_rules)
+                .find(|(first, second, _)| *first == opcode && next == Some(*second as u8));
+
+            match rule {
+                Some((_, second, replacement)) => {
+                    let line = match second {
+                        Opcode::Not => line,
+                        _ => chunk.line_for_instruction_n(offset + length + 1),
+                    };
+                    let mut instruction = vec![*replacement as u8];
+                    instruction.extend_from_slice(&chunk.code[offset + 1..offset + length]);
+                    optimized.write(instruction, line);
+                    offset += length + 1 + second.operand_len();
+                }
+                None => {
+                    optimized.write(&chunk.code[offset..offset + length], line);
+                    offset += length;
+                }
+            }
+        }
+
+        optimized
+    }
+}
+
+fn fold(expr: Expr) -> Expr {
+    let span = expr.span;
+
+    match expr.kind {
+        ExprKind::Grouping(inner) => {
+            let inner = fold(*inner);
+            match inner.kind {
+                ExprKind::Literal(_) => inner,
+                _ => Expr {
+                    kind: ExprKind::Grouping(Box::new(inner)),
+                    span,
+                },
+            }
+        }
+        ExprKind::Unary {
+            operator,
+            operator_span,
+            operand,
+        } => {
+            let operand = fold(*operand);
+            let result = match &operand.kind {
+                ExprKind::Literal(literal) => unary(operator, &to_value(literal)),
+                _ => None,
+            };
+
+            match result {
+                Some(value) => literal(value, span),
+                None => Expr {
+                    kind: ExprKind::Unary {
+                        operator,
+                        operator_span,
+                        operand: Box::new(operand),
+                    },
+                    span,
+                },
+            }
+        }
+        ExprKind::Binary {
+            left,
+            operator,
+            operator_span,
+            right,
+        } => {
+            let left = fold(*left);
+            let right = fold(*right);
+            let result = match (&left.kind, &right.kind) {
+                (ExprKind::Literal(a), ExprKind::Literal(b)) => {
+                    binary(operator, &to_value(a), &to_value(b))
+                }
+                _ => None,
+            };
+
+            match result {
+                Some(value) => literal(value, span),
+                None => Expr {
+                    kind: ExprKind::Binary {
+                        left: Box::new(left),
+                        operator,
+                        operator_span,
+                        right: Box::new(right),
+                    },
+                    span,
+                },
+            }
+        }
+        kind => Expr { kind, span },
+    }
+}
+
+fn unary(operator: UnaryOperator, operand: &Value) -> Option<Value> {
+    match operator {
+        UnaryOperator::Negate => operand.as_f64().map(|n| Value::number(-n)),
+        UnaryOperator::Not => Some(Value::bool(operand.is_falsey())),
+    }
+}
+
+// NOTE: The compiler emits `!=`, `>=` and `<=` as the opposite comparison followed by `Not`, so
+//       they are folded the same way. `NaN >= 1` is true at runtime, for example.
+fn binary(operator: BinaryOperator, a: &Value, b: &Value) -> Option<Value> {
+    let numbers = a.as_f64().zip(b.as_f64());
+
+    match operator {
+        BinaryOperator::Add => match (a.kind(), b.kind()) {
+            (ValueKind::Obj(Obj::String(a)), ValueKind::Obj(Obj::String(b))) => {
+                Some(Value::obj(Obj::String(format!("{}{}", a, b))))
+            }
+            _ => numbers.map(|(a, b)| Value::number(a + b)),
+        },
+        BinaryOperator::Subtract => numbers.map(|(a, b)| Value::number(a - b)),
+        BinaryOperator::Multiply => numbers.map(|(a, b)| Value::number(a * b)),
+        BinaryOperator::Divide => numbers.map(|(a, b)| Value::number(a / b)),
+        BinaryOperator::Equal => Some(Value::bool(a == b)),
+        BinaryOperator::NotEqual => Some(Value::bool(!(a == b))),
+        BinaryOperator::Greater => numbers.map(|(a, b)| Value::bool(a > b)),
+        BinaryOperator::GreaterEqual => {
+            numbers.map(|(a, b)| Value::bool(a.partial_cmp(&b) != Some(Ordering::Less)))
+        }
+        BinaryOperator::Less => numbers.map(|(a, b)| Value::bool(a < b)),
+        BinaryOperator::LessEqual => {
+            numbers.map(|(a, b)| Value::bool(a.partial_cmp(&b) != Some(Ordering::Greater)))
+        }
+    }
+}
+
+fn to_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Number(n) => Value::number(*n),
+        Literal::String(s) => Value::obj(Obj::String(s.clone())),
+        Literal::True => Value::bool(true),
+        Literal::False => Value::bool(false),
+        Literal::Nil => Value::nil(),
+    }
+}
+
+fn literal(value: Value, span: Span) -> Expr {
+    let literal = match value.kind() {
+        ValueKind::Number(n) => Literal::Number(n),
+        ValueKind::Bool(true) => Literal::True,
+        ValueKind::Bool(false) => Literal::False,
+        ValueKind::Nil => Literal::Nil,
+        ValueKind::Obj(Obj::String(s)) => Literal::String(s.clone()),
+        ValueKind::Obj(Obj::Error(_)) => unreachable!(),
+    };
+
+    Expr {
+        kind: ExprKind::Literal(literal),
+        span,
+    }
+}
@@ -0,0 +1,24 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+/// Collects what the VM writes so that it can be read back afterwards, for hosts that can't let
+/// it go straight to stdout. Clones share the same buffer, so one can be handed to the VM while
+/// another is kept to read from.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use std::{borrow::Cow, io::IsTerminal, path::PathBuf};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+
+use crate::{
+    chunk::Chunk,
+    load_chunk,
+    scanner::{Scanner, TokenCategory, TokenType, KEYWORDS},
+    vm::{InterpretError, Vm},
+};
+
+/// Where history is kept, under the user's home directory.
+pub const HISTORY_FILE: &str = ".lox_history";
+
+const HELP: &str = "\
+Commands:
+  :dis            Disassemble the last input that compiled
+  :globals        List the global variables
+  :load <path>    Run a Lox source, .loxasm or .loxb file
+  :reset          Throw away everything the VM is holding on to
+  :help           Show this list
+  :quit           Leave the REPL, as does Ctrl-D";
+
+// ANSI escape codes for the color of each category of token.
+const KEYWORD_COLOR: &str = "\x1b[35m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const OPERATOR_COLOR: &str = "\x1b[36m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+type LoxEditor = Editor<LoxHelper, DefaultHistory>;
+
+pub struct Repl {
+    vm: Vm,
+    last_chunk: Option<Chunk>,
+}
+
+impl Repl {
+    pub fn new(vm: Vm) -> Self {
+        Repl {
+            vm,
+            last_chunk: None,
+        }
+    }
+
+    /// Reads and runs input until Ctrl-D or `:quit`. Errors in the input are reported by the VM
+    /// and the session carries on; only failing to read input or write history ends it early.
+    pub fn run(&mut self) -> std::io::Result<()> {
+        let mut editor = LoxEditor::new().map_err(Self::io_error)?;
+        editor.set_helper(Some(LoxHelper::new(LoxHelper::color_enabled())));
+        let history = Self::history_path();
+        if let Some(path) = &history {
+            // NOTE: There is no history file until the first session has ended.
+            let _ = editor.load_history(path);
+        }
+
+        while let Some(input) = Self::read_input(&mut editor).map_err(Self::io_error)? {
+            let input = input.trim_end();
+            if input.is_empty() {
+                continue;
+            }
+            editor.add_history_entry(input).map_err(Self::io_error)?;
+
+            let command = match input.strip_prefix(':') {
+                Some(command) => command.trim(),
+                None => {
+                    self.interpret(input);
+                    continue;
+                }
+            };
+            let (command, argument) = match command.split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (command, ""),
+            };
+
+            match command {
+                "dis" => match &self.last_chunk {
+                    Some(chunk) => print!("{:?}", chunk),
+                    None => println!("Nothing has been compiled yet"),
+                },
+                // NOTE: Lox is only expressions so far, so nothing can define a global.
+                "globals" => println!("No globals are defined"),
+                "load" if argument.is_empty() => println!("Usage: :load <path>"),
+                "load" => self.load(argument),
+                "reset" => {
+                    self.vm.reset();
+                    self.last_chunk = None;
+                }
+                "help" => println!("{}", HELP),
+                "quit" => break,
+                _ => println!("Unknown command ':{}', type :help for a list", command),
+            }
+        }
+
+        if let Some(path) = &history {
+            editor.save_history(path).map_err(Self::io_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether every parenthesis and brace opened in source has been closed, so that it can be
+    /// run rather than waiting for another line.
+    pub fn is_complete(source: &str) -> bool {
+        let mut scanner = Scanner::new(source);
+        let mut depth = 0;
+
+        // NOTE: Scanner errors are left for the compiler to report.
+        while let Ok(token) = scanner.scan_token() {
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+                TokenType::Eof => break,
+                _ => {}
+            }
+        }
+
+        depth <= 0
+    }
+
+    // Reads lines until they make up complete input, or returns None at the end of input. Ctrl-C
+    // throws away what has been typed so far.
+    fn read_input(editor: &mut LoxEditor) -> rustyline::Result<Option<String>> {
+        let mut input = String::new();
+
+        loop {
+            let prompt = match input.is_empty() {
+                true => "> ",
+                false => "... ",
+            };
+
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    input.push_str(&line);
+                    input.push('\n');
+                }
+                Err(ReadlineError::Interrupted) => {
+                    input.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) if input.is_empty() => return Ok(None),
+                Err(ReadlineError::Eof) => return Ok(Some(input)),
+                Err(error) => return Err(error),
+            }
+
+            if Self::is_complete(&input) {
+                return Ok(Some(input));
+            }
+        }
+    }
+
+    fn interpret(&mut self, source: &str) {
+        // NOTE: Errors have already been written to the VM's error output, and shouldn't end
+        //       the session.
+        if let Ok(chunk) = self.vm.compile(source) {
+            if let Err(error) = self.run_chunk(chunk) {
+                eprintln!("{}", error);
+            }
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        let optimizations = self.vm.optimizations();
+        let typecheck = self.vm.typecheck();
+        let chunk = match load_chunk(path, optimizations, typecheck, &mut std::io::stderr()) {
+            Ok((chunk, _)) => chunk,
+            Err(error) => {
+                if !matches!(error.downcast_ref(), Some(InterpretError::CompileError)) {
+                    eprintln!("Could not load '{}': {}", path, error);
+                }
+                return;
+            }
+        };
+
+        if let Err(error) = self.run_chunk(chunk) {
+            eprintln!("Could not run '{}': {}", path, error);
+        }
+    }
+
+    // Runs a chunk, keeping it for `:dis`. Runtime errors have already been written to the VM's
+    // error output, so only the errors that haven't been, such as invalid bytecode, come back.
+    fn run_chunk(&mut self, chunk: Chunk) -> Result<(), InterpretError> {
+        self.last_chunk = Some(chunk.clone());
+        match self.vm.interpret_chunk(chunk) {
+            Err(InterpretError::RuntimeError(_)) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn history_path() -> Option<PathBuf> {
+        std::env::home_dir().map(|home| home.join(HISTORY_FILE))
+    }
+
+    fn io_error(error: ReadlineError) -> std::io::Error {
+        match error {
+            ReadlineError::Io(error) => error,
+            error => std::io::Error::other(error),
+        }
+    }
+}
+
+/// Highlights and completes Lox as it is typed into the REPL.
+pub struct LoxHelper {
+    color: bool,
+}
+
+impl LoxHelper {
+    pub fn new(color: bool) -> Self {
+        LoxHelper { color }
+    }
+
+    /// Whether to highlight input, which is only when stdout is a terminal and `NO_COLOR` is
+    /// not set.
+    pub fn color_enabled() -> bool {
+        std::io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    }
+
+    /// Wraps each keyword, string, number and operator in source in the color for its category.
+    /// Everything else, whitespace and comments included, is left as it is.
+    pub fn colorize(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let mut colored = String::with_capacity(source.len());
+        let mut end = 0;
+
+        loop {
+            // NOTE: Characters the scanner rejects are left uncolored.
+            let Ok(token) = scanner.scan_token() else {
+                continue;
+            };
+            let color = match token.token_type.category() {
+                _ if token.token_type == TokenType::Eof => break,
+                Some(TokenCategory::Keyword) => KEYWORD_COLOR,
+                Some(TokenCategory::String) => STRING_COLOR,
+                Some(TokenCategory::Number) => NUMBER_COLOR,
+                Some(TokenCategory::Operator) => OPERATOR_COLOR,
+                Some(TokenCategory::Identifier) | None => continue,
+            };
+
+            colored.push_str(&source[end..token.start]);
+            colored.push_str(color);
+            colored.push_str(token.lexeme(source));
+            colored.push_str(RESET_COLOR);
+            end = token.start + token.length;
+        }
+
+        colored.push_str(&source[end..]);
+        colored
+    }
+
+    /// The keywords that could finish the word that ends at pos, and where that word starts.
+    ///
+    /// NOTE: There are no globals to offer alongside them until the language can declare any.
+    pub fn complete_word(line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+            .len();
+        let word = &line[start..pos];
+        if word.is_empty() {
+            return (start, Vec::new());
+        }
+
+        let candidates = KEYWORDS
+            .iter()
+            .filter(|keyword| keyword.starts_with(word))
+            .map(|keyword| keyword.to_string())
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(Self::complete_word(line, pos))
+    }
+}
+
+impl Highlighter for LoxHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        match self.color {
+            true => Cow::Owned(Self::colorize(line)),
+            false => Cow::Borrowed(line),
+        }
+    }
+
+    // NOTE: Any character can change how the rest of the line scans, such as a quote starting
+    //       a string, so the whole line is highlighted again after every one.
+    fn highlight_char(&self, _: &str, _: usize, _: bool) -> bool {
+        self.color
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::LoxHelper;
+
+    #[test]
+    fn colors_tokens_by_category() {
+        assert_eq!(
+            LoxHelper::colorize("!true == (1 + \"a\") // done"),
+            "\x1b[36m!\x1b[0m\x1b[35mtrue\x1b[0m \x1b[36m==\x1b[0m (\x1b[33m1\x1b[0m \
+             \x1b[36m+\x1b[0m \x1b[32m\"a\"\x1b[0m) // done"
+        );
+        // An unterminated string is left for the compiler to report.
+        assert_eq!(LoxHelper::colorize("1 \"a"), "\x1b[33m1\x1b[0m \"a");
+    }
+
+    // Multi-byte characters before a token don't move where its color goes.
+    #[test]
+    fn colors_tokens_after_non_ascii_text() {
+        assert_eq!(
+            LoxHelper::colorize("\"café\" + naïve ☕ 1 // ünï"),
+            "\x1b[32m\"café\"\x1b[0m \x1b[36m+\x1b[0m naïve ☕ \x1b[33m1\x1b[0m // ünï"
+        );
+    }
+
+    #[test]
+    fn completes_keywords() {
+        assert_eq!(
+            LoxHelper::complete_word("!tr", 3),
+            (1, vec!["true".to_string()])
+        );
+        assert_eq!(
+            LoxHelper::complete_word("tr + 1", 2),
+            (0, vec!["true".to_string()])
+        );
+        // Keywords are offered in alphabetical order.
+        assert_eq!(
+            LoxHelper::complete_word("(f", 2),
+            (1, vec!["false".into(), "for".into(), "fun".into()])
+        );
+        // With nothing to complete, nothing is offered.
+        assert_eq!(LoxHelper::complete_word("1 + ", 4), (4, Vec::new()));
+        assert_eq!(LoxHelper::complete_word("naï", 4), (0, Vec::new()));
+    }
+}
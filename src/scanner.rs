@@ -1,8 +1,15 @@
+pub const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+/// Splits source into tokens. Offsets into the source, in tokens and here, count bytes.
 pub struct Scanner<'src> {
     source: &'src str,
     start: usize,
     current: usize,
     line: usize,
+    comments: Vec<Token>,
 }
 
 macro_rules! match_or {
@@ -23,9 +30,16 @@ impl<'src> Scanner<'src> {
             start: 0,
             current: 0,
             line: 1,
+            comments: Vec::new(),
         }
     }
 
+    /// The comments skipped over so far, in source order. The compiler never sees them, but the
+    /// formatter needs them to write them back out.
+    pub fn comments(&self) -> &[Token] {
+        &self.comments
+    }
+
     pub fn scan_token(&mut self) -> Result<Token, CompilerError> {
         self.skip_whitespace();
         self.start = self.current;
@@ -72,14 +86,12 @@ impl<'src> Scanner<'src> {
                     self.line += 1;
                     self.advance();
                 }
-                '/' => {
-                    if self.peek_next() == '/' {
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                    } else {
-                        return;
+                '/' if self.peek_next() == '/' => {
+                    self.start = self.current;
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
                     }
+                    self.comments.push(self.make_token(TokenType::Comment));
                 }
                 _ => return,
             }
@@ -95,34 +107,29 @@ impl<'src> Scanner<'src> {
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        let c = self.peek();
+        self.current += c.len_utf8();
+        c
     }
 
     fn match_char(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
+        if self.peek() != expected {
             return false;
         }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn string(&mut self) -> Result<Token, CompilerError> {
@@ -161,21 +168,21 @@ impl<'src> Scanner<'src> {
     }
 
     fn identifier(&mut self) -> Result<Token, CompilerError> {
-        while self.peek().is_alphabetic() || self.peek().is_digit(10) || self.peek() == '_' {
+        while self.peek().is_alphabetic() || self.peek().is_ascii_digit() || self.peek() == '_' {
             self.advance();
         }
         Ok(self.make_token(self.identifier_type()))
     }
 
     fn identifier_type(&self) -> TokenType {
-        match self.source.chars().nth(self.start) {
+        match self.source[self.start..].chars().next() {
             Some(c) => match c {
                 'a' => self.check_keyword(1, 2, "nd", TokenType::And),
                 'c' => self.check_keyword(1, 4, "lass", TokenType::Class),
                 'e' => self.check_keyword(1, 3, "lse", TokenType::Else),
                 'f' => {
                     if self.current - self.start > 1 {
-                        match self.source.chars().nth(self.start + 1).unwrap() {
+                        match self.source[self.start + 1..].chars().next().unwrap() {
                             'a' => self.check_keyword(2, 3, "lse", TokenType::False),
                             'o' => self.check_keyword(2, 1, "r", TokenType::For),
                             'u' => self.check_keyword(2, 1, "n", TokenType::Fun),
@@ -193,7 +200,7 @@ impl<'src> Scanner<'src> {
                 's' => self.check_keyword(1, 4, "uper", TokenType::Super),
                 't' => {
                     if self.current - self.start > 1 {
-                        match self.source.chars().nth(self.start + 1).unwrap() {
+                        match self.source[self.start + 1..].chars().next().unwrap() {
                             'h' => self.check_keyword(2, 2, "is", TokenType::This),
                             'r' => self.check_keyword(2, 2, "ue", TokenType::True),
                             _ => TokenType::Identifier,
@@ -295,10 +302,62 @@ pub enum TokenType {
     Var,
     While,
 
+    Comment,
     Error,
     Eof,
 }
 
+/// The broad kinds of token that editors highlight differently.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TokenCategory {
+    Keyword,
+    String,
+    Number,
+    Operator,
+    Identifier,
+}
+
+impl TokenType {
+    /// The category to highlight this type of token as, or None for punctuation and the rest.
+    pub fn category(self) -> Option<TokenCategory> {
+        let category = match self {
+            TokenType::And
+            | TokenType::Class
+            | TokenType::Else
+            | TokenType::False
+            | TokenType::For
+            | TokenType::Fun
+            | TokenType::If
+            | TokenType::Nil
+            | TokenType::Or
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Super
+            | TokenType::This
+            | TokenType::True
+            | TokenType::Var
+            | TokenType::While => TokenCategory::Keyword,
+            TokenType::String => TokenCategory::String,
+            TokenType::Number => TokenCategory::Number,
+            TokenType::Minus
+            | TokenType::Plus
+            | TokenType::Slash
+            | TokenType::Star
+            | TokenType::Bang
+            | TokenType::BangEqual
+            | TokenType::Equal
+            | TokenType::EqualEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => TokenCategory::Operator,
+            TokenType::Identifier => TokenCategory::Identifier,
+            _ => return None,
+        };
+        Some(category)
+    }
+}
+
 pub struct CompilerError {
     pub line: usize,
     pub message: String,
@@ -0,0 +1,308 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    chunk::Chunk,
+    value::{ErrorObj, Obj, Value, ValueKind},
+};
+
+// File layout:
+//
+//   magic     4 bytes  "LOXB"
+//   version   u16
+//   checksum  u32      CRC-32 of the payload
+//   length    u32      length of the payload in bytes
+//   payload            the chunk, see `write_chunk`
+//
+// All integers are big endian, to match how `ConstantLong` operands are written into `code`.
+pub const MAGIC: &[u8; 4] = b"LOXB";
+pub const VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 4;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_NIL: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_ERROR: u8 = 4;
+
+pub struct Serializer {}
+
+impl Serializer {
+    pub fn serialize(chunk: &Chunk) -> Vec<u8> {
+        let mut payload = Vec::new();
+        Self::write_chunk(chunk, &mut payload);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_be_bytes());
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Chunk, SerializeError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.read_bytes(MAGIC.len())? != MAGIC {
+            return Err(SerializeError::BadMagic);
+        }
+
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(SerializeError::UnsupportedVersion(version));
+        }
+
+        let checksum = reader.read_u32()?;
+        let length = reader.read_u32()? as usize;
+        let payload = &bytes[reader.offset..];
+        if payload.len() < length {
+            return Err(SerializeError::UnexpectedEnd(bytes.len()));
+        }
+        if payload.len() > length {
+            return Err(SerializeError::TrailingBytes(reader.offset + length));
+        }
+        if crc32(payload) != checksum {
+            return Err(SerializeError::ChecksumMismatch);
+        }
+
+        let chunk = Self::read_chunk(&mut reader)?;
+        if !reader.is_at_end() {
+            return Err(SerializeError::TrailingBytes(reader.offset));
+        }
+
+        Ok(chunk)
+    }
+
+    // Chunk layout:
+    //
+    //   name       u32 length, then UTF-8 bytes
+    //   code       u32 length, then the bytes
+    //   lines      u32 count, then (u32 line, u32 offset) pairs
+    //   constants  u32 count, then each value as a one byte tag followed by its contents
+    fn write_chunk(chunk: &Chunk, out: &mut Vec<u8>) {
+        write_str(&chunk.name, out);
+
+        write_u32(chunk.code.len(), out);
+        out.extend_from_slice(&chunk.code);
+
+        write_u32(chunk.lines.len(), out);
+        for (line, offset) in &chunk.lines {
+            write_u32(*line, out);
+            write_u32(*offset, out);
+        }
+
+        write_u32(chunk.constants.len(), out);
+        for constant in &chunk.constants {
+            Self::write_value(constant, out);
+        }
+    }
+
+    fn write_value(value: &Value, out: &mut Vec<u8>) {
+        match value.kind() {
+            ValueKind::Number(n) => {
+                out.push(TAG_NUMBER);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+            ValueKind::Bool(b) => {
+                out.push(TAG_BOOL);
+                out.push(b as u8);
+            }
+            ValueKind::Nil => out.push(TAG_NIL),
+            ValueKind::Obj(Obj::String(s)) => {
+                out.push(TAG_STRING);
+                write_str(s, out);
+            }
+            ValueKind::Obj(Obj::Error(error)) => {
+                out.push(TAG_ERROR);
+                write_str(&error.message, out);
+                write_u32(error.line, out);
+                write_u32(error.trace.len(), out);
+                for frame in &error.trace {
+                    write_str(frame, out);
+                }
+            }
+        }
+    }
+
+    fn read_chunk(reader: &mut Reader) -> Result<Chunk, SerializeError> {
+        let mut chunk = Chunk::new(reader.read_str()?);
+
+        let code_len = reader.read_u32()? as usize;
+        chunk.code = reader.read_bytes(code_len)?.to_vec();
+
+        let line_count = reader.read_u32()? as usize;
+        let mut previous_offset = None;
+        for _ in 0..line_count {
+            let line = reader.read_u32()? as usize;
+            let offset_at = reader.offset;
+            let offset = reader.read_u32()? as usize;
+
+            // Each run in the table starts at a later instruction than the one before it.
+            if offset >= chunk.code.len() || previous_offset.is_some_and(|p| offset <= p) {
+                return Err(SerializeError::InvalidLineOffset(offset_at));
+            }
+            previous_offset = Some(offset);
+
+            chunk.lines.push((line, offset));
+        }
+
+        let constant_count = reader.read_u32()? as usize;
+        for _ in 0..constant_count {
+            let value = Self::read_value(reader)?;
+            chunk.constants.push(value);
+        }
+
+        Ok(chunk)
+    }
+
+    fn read_value(reader: &mut Reader) -> Result<Value, SerializeError> {
+        let tag_at = reader.offset;
+        let value = match reader.read_u8()? {
+            TAG_NUMBER => {
+                let bytes = reader.read_bytes(8)?;
+                Value::number(f64::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            TAG_BOOL => match reader.read_u8()? {
+                0 => Value::bool(false),
+                1 => Value::bool(true),
+                _ => return Err(SerializeError::InvalidBool(reader.offset - 1)),
+            },
+            TAG_NIL => Value::nil(),
+            TAG_STRING => Value::obj(Obj::String(reader.read_str()?)),
+            TAG_ERROR => {
+                let message = reader.read_str()?;
+                let line = reader.read_u32()? as usize;
+                let frame_count = reader.read_u32()? as usize;
+                let mut trace = Vec::new();
+                for _ in 0..frame_count {
+                    trace.push(reader.read_str()?);
+                }
+                Value::obj(Obj::Error(Box::new(ErrorObj::new(&message, line, trace))))
+            }
+            tag => return Err(SerializeError::UnknownValueTag(tag, tag_at)),
+        };
+
+        Ok(value)
+    }
+}
+
+fn write_u32(n: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(n as u32).to_be_bytes());
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_u32(s.len(), out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, offset: 0 }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], SerializeError> {
+        // NOTE: Lengths come straight from the file, so check them before slicing rather than
+        //       trusting them not to run off the end.
+        if length > self.bytes.len() - self.offset {
+            return Err(SerializeError::UnexpectedEnd(self.offset));
+        }
+
+        let bytes = &self.bytes[self.offset..self.offset + length];
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SerializeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, SerializeError> {
+        Ok(u16::from_be_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, SerializeError> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_str(&mut self) -> Result<String, SerializeError> {
+        let length = self.read_u32()? as usize;
+        let start = self.offset;
+        let bytes = self.read_bytes(length)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => Err(SerializeError::InvalidString(start)),
+        }
+    }
+}
+
+/// The CRC-32 (IEEE) of bytes, as a .loxb header gives for its payload.
+///
+/// NOTE: Bit at a time. Bytecode files are small enough that a lookup table isn't worth it.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SerializeError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    UnexpectedEnd(usize),
+    TrailingBytes(usize),
+    InvalidString(usize),
+    InvalidBool(usize),
+    InvalidLineOffset(usize),
+    UnknownValueTag(u8, usize),
+}
+
+impl Display for SerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerializeError::BadMagic => write!(f, "Not a Lox bytecode file"),
+            SerializeError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported bytecode version {} (expected {})",
+                version, VERSION
+            ),
+            SerializeError::ChecksumMismatch => write!(f, "Bytecode checksum does not match"),
+            SerializeError::UnexpectedEnd(offset) => {
+                write!(f, "Unexpected end of bytecode at byte {}", offset)
+            }
+            SerializeError::TrailingBytes(offset) => {
+                write!(f, "Unexpected trailing bytes at byte {}", offset)
+            }
+            SerializeError::InvalidString(offset) => {
+                write!(f, "Invalid UTF-8 string at byte {}", offset)
+            }
+            SerializeError::InvalidBool(offset) => {
+                write!(f, "Invalid boolean at byte {}", offset)
+            }
+            SerializeError::InvalidLineOffset(offset) => {
+                write!(f, "Invalid line table offset at byte {}", offset)
+            }
+            SerializeError::UnknownValueTag(tag, offset) => {
+                write!(f, "Unknown value tag {} at byte {}", tag, offset)
+            }
+        }
+    }
+}
+
+impl Error for SerializeError {}
@@ -0,0 +1,55 @@
+use std::io::{BufRead, Write};
+
+use serde_json::Value as Json;
+
+// Both the debug adapter and language server protocols send JSON bodies framed by a
+// Content-Length header, in the style of HTTP.
+
+/// Reads the next message, or `None` at the end of input. A body that isn't JSON is handed back
+/// as an error of its own, as the next message can still be read after it.
+pub fn read_message<R: BufRead>(
+    input: &mut R,
+) -> std::io::Result<Option<Result<Json, serde_json::Error>>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let content_length = match content_length {
+        Some(length) => length,
+        None => return Err(invalid_data("Message is missing a Content-Length header")),
+    };
+
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    Ok(Some(serde_json::from_slice(&content)))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> std::io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
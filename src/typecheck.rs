@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use crate::{
+    ast::{BinaryOperator, Expr, ExprKind, Literal, Script, Span, UnaryOperator},
+    compiler::Diagnostic,
+    scanner::{Token, TokenType},
+};
+
+/// The type of an expression as far as the checker can tell. `Any` is for values whose type
+/// isn't known until runtime, and is accepted wherever a type is expected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Number,
+    String,
+    Bool,
+    Nil,
+    Any,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Type::Number => "num",
+            Type::String => "str",
+            Type::Bool => "bool",
+            Type::Nil => "nil",
+            Type::Any => "any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Type {
+    fn is(self, expected: Type) -> bool {
+        self == expected || self == Type::Any
+    }
+}
+
+/// A gradual type checker, which reports operations that are bound to fail at runtime with
+/// "Operand must be a number" and the like as compile errors instead.
+///
+/// NOTE: There are no declarations yet, so there is nowhere to write a type annotation and
+///       every type comes from a literal. `Any` only stands in for an operation that has
+///       already been reported, so that one mistake isn't reported again by everything around it.
+pub struct TypeChecker {
+    diagnostics: Vec<Diagnostic>,
+    types: Vec<(Span, Type)>,
+}
+
+impl TypeChecker {
+    pub fn check(script: &Script) -> Vec<Diagnostic> {
+        Self::run(script).diagnostics
+    }
+
+    /// The type of every expression in a script, inner expressions before the ones around them.
+    pub fn types(script: &Script) -> Vec<(Span, Type)> {
+        Self::run(script).types
+    }
+
+    fn run(script: &Script) -> TypeChecker {
+        let mut checker = TypeChecker {
+            diagnostics: Vec::new(),
+            types: Vec::new(),
+        };
+        checker.visit(&script.body);
+
+        checker
+    }
+
+    fn visit(&mut self, expr: &Expr) -> Type {
+        let expr_type = self.infer(expr);
+        self.types.push((expr.span, expr_type));
+
+        expr_type
+    }
+
+    fn infer(&mut self, expr: &Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Literal(literal) => match literal {
+                Literal::Number(_) => Type::Number,
+                Literal::String(_) => Type::String,
+                Literal::True | Literal::False => Type::Bool,
+                Literal::Nil => Type::Nil,
+            },
+            ExprKind::Grouping(inner) => self.visit(inner),
+            ExprKind::Unary {
+                operator,
+                operator_span,
+                operand,
+            } => {
+                let operand = self.visit(operand);
+                match operator {
+                    UnaryOperator::Negate if operand.is(Type::Number) => Type::Number,
+                    UnaryOperator::Negate => self.error(
+                        *operator_span,
+                        TokenType::Minus,
+                        format!("Operand must be a number, but found {}.", operand),
+                    ),
+                    UnaryOperator::Not => Type::Bool,
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                operator_span,
+                right,
+            } => {
+                let (left, right) = (self.visit(left), self.visit(right));
+                self.check_binary(*operator, *operator_span, left, right)
+            }
+            ExprKind::Invalid => Type::Any,
+        }
+    }
+
+    fn check_binary(
+        &mut self,
+        operator: BinaryOperator,
+        operator_span: Span,
+        left: Type,
+        right: Type,
+    ) -> Type {
+        let numbers = left.is(Type::Number) && right.is(Type::Number);
+
+        let message = match operator {
+            BinaryOperator::Equal | BinaryOperator::NotEqual => return Type::Bool,
+            BinaryOperator::Add => match (left, right) {
+                (Type::Any, Type::Any) => return Type::Any,
+                _ if numbers => return Type::Number,
+                _ if left.is(Type::String) && right.is(Type::String) => return Type::String,
+                _ => "Operands must be two numbers or two strings",
+            },
+            BinaryOperator::Subtract | BinaryOperator::Multiply | BinaryOperator::Divide
+                if numbers =>
+            {
+                return Type::Number
+            }
+            _ if numbers => return Type::Bool,
+            _ => "Operands must be numbers",
+        };
+
+        self.error(
+            operator_span,
+            operator.token_type(),
+            format!("{}, but found {} and {}.", message, left, right),
+        )
+    }
+
+    fn error(&mut self, span: Span, token_type: TokenType, message: String) -> Type {
+        self.diagnostics.push(Diagnostic {
+            line: span.line,
+            token: Some(Token::new(token_type, span.start, span.length, span.line)),
+            message,
+        });
+
+        Type::Any
+    }
+}
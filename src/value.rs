@@ -1,66 +1,79 @@
-use std::fmt::Display;
+use std::{
+    error::Error,
+    fmt::{Debug, Display},
+};
 
-#[derive(Debug, Clone)]
-pub enum Value {
+#[cfg(feature = "nan-boxing")]
+mod nan_boxed;
+#[cfg(not(feature = "nan-boxing"))]
+mod tagged;
+
+#[cfg(feature = "nan-boxing")]
+pub use nan_boxed::Value;
+#[cfg(not(feature = "nan-boxing"))]
+pub use tagged::Value;
+
+/// A value taken apart, for matching on. Both representations of `Value` hand these out, so
+/// nothing outside this module depends on which one is in use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind<'a> {
     Number(f64),
     Bool(bool),
     Nil,
-    Obj(Obj),
+    Obj(&'a Obj),
 }
 
 impl Value {
     pub fn as_f64(&self) -> Option<f64> {
-        match self {
-            Value::Number(n) => Some(*n),
+        match self.kind() {
+            ValueKind::Number(n) => Some(n),
             _ => None,
         }
     }
 
     pub fn as_bool(&self) -> Option<bool> {
-        match self {
-            Value::Bool(b) => Some(*b),
+        match self.kind() {
+            ValueKind::Bool(b) => Some(b),
             _ => None,
         }
     }
 
     pub fn is_number(&self) -> bool {
-        match self {
-            Value::Number(_) => true,
-            _ => false,
-        }
+        matches!(self.kind(), ValueKind::Number(_))
     }
 
     pub fn is_bool(&self) -> bool {
-        match self {
-            Value::Bool(_) => true,
-            _ => false,
-        }
+        matches!(self.kind(), ValueKind::Bool(_))
     }
 
     pub fn is_nil(&self) -> bool {
-        match self {
-            Value::Nil => true,
-            _ => false,
-        }
+        matches!(self.kind(), ValueKind::Nil)
     }
 
     pub fn is_falsey(&self) -> bool {
-        match self {
-            Value::Nil | Value::Bool(false) => true,
+        matches!(self.kind(), ValueKind::Nil | ValueKind::Bool(false))
+    }
+
+    pub(crate) fn is_obj_type(&self, obj_type: ObjType) -> bool {
+        match self.kind() {
+            ValueKind::Obj(obj) => obj.is_type(obj_type),
             _ => false,
         }
     }
 
-    pub fn is_obj_type(&self, obj_type: ObjType) -> bool {
-        match self {
-            Value::Obj(obj) => obj.is_type(obj_type),
-            _ => false,
+    pub fn type_name(&self) -> &'static str {
+        match self.kind() {
+            ValueKind::Number(_) => "number",
+            ValueKind::Bool(_) => "bool",
+            ValueKind::Nil => "nil",
+            ValueKind::Obj(Obj::String(_)) => "string",
+            ValueKind::Obj(Obj::Error(_)) => "error",
         }
     }
 
-    pub fn as_string(self) -> Option<String> {
-        match self {
-            Value::Obj(Obj::String(s)) => Some(s),
+    pub fn into_string(self) -> Option<String> {
+        match self.into_obj() {
+            Some(Obj::String(s)) => Some(s),
             _ => None,
         }
     }
@@ -68,35 +81,125 @@ impl Value {
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => a == b,
-            (Value::Bool(a), Value::Bool(b)) => a == b,
-            (Value::Nil, Value::Nil) => true,
+        match (self.kind(), other.kind()) {
+            (ValueKind::Number(a), ValueKind::Number(b)) => a == b,
+            (ValueKind::Bool(a), ValueKind::Bool(b)) => a == b,
+            (ValueKind::Nil, ValueKind::Nil) => true,
             _ => false,
         }
     }
 }
 
+impl Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::Obj(Obj::String(s)) => write!(f, "{}", s),
+        match self.kind() {
+            ValueKind::Number(n) => write!(f, "{}", n),
+            ValueKind::Bool(b) => write!(f, "{}", b),
+            ValueKind::Nil => write!(f, "nil"),
+            ValueKind::Obj(Obj::String(s)) => write!(f, "{}", s),
+            ValueKind::Obj(Obj::Error(e)) => write!(f, "{}", e),
         }
     }
 }
 
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::obj(Obj::String(s))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::obj(Obj::String(s.to_string()))
+    }
+}
+
+// `None` is nil.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::nil(), Into::into)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ValueTypeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or(ValueTypeError::new("number", &value))
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ValueTypeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_bool().ok_or(ValueTypeError::new("bool", &value))
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ValueTypeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let error = ValueTypeError::new("string", &value);
+        value.into_string().ok_or(error)
+    }
+}
+
+/// A value converted to a Rust type it doesn't hold.
+#[derive(Debug, PartialEq)]
+pub struct ValueTypeError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl ValueTypeError {
+    fn new(expected: &'static str, value: &Value) -> Self {
+        ValueTypeError {
+            expected,
+            found: value.type_name(),
+        }
+    }
+}
+
+impl Display for ValueTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected {} but found {}", self.expected, self.found)
+    }
+}
+
+impl Error for ValueTypeError {}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Obj {
     String(String),
+    // NOTE: Boxed so that an error doesn't make every other value as big as it is.
+    Error(Box<ErrorObj>),
 }
 
 impl Obj {
     fn is_type(&self, obj_type: ObjType) -> bool {
         match self {
             Obj::String(_) => obj_type == ObjType::String,
+            Obj::Error(_) => obj_type == ObjType::Error,
         }
     }
 }
@@ -104,4 +207,30 @@ impl Obj {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ObjType {
     String,
+    Error,
+}
+
+// NOTE: Errors raised by the VM itself (e.g. "Operands must be numbers") are built as one of these
+//       so that they are ordinary values, rather than something that only exists on the Rust side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorObj {
+    pub message: String,
+    pub line: usize,
+    pub trace: Vec<String>,
+}
+
+impl ErrorObj {
+    pub fn new(message: &str, line: usize, trace: Vec<String>) -> Self {
+        ErrorObj {
+            message: message.to_string(),
+            line,
+            trace,
+        }
+    }
+}
+
+impl Display for ErrorObj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
@@ -0,0 +1,130 @@
+use std::{marker::PhantomData, mem::ManuallyDrop, rc::Rc};
+
+use super::{Obj, ValueKind};
+
+// Every bit of the exponent plus the two highest bits of the mantissa. No number the VM makes
+// has all of these set, see `Value::number`, so anything that does is one of the other kinds.
+const QNAN: u64 = 0x7ffc_0000_0000_0000;
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+
+const TAG_NIL: u64 = 1;
+const TAG_FALSE: u64 = 2;
+const TAG_TRUE: u64 = 3;
+
+// The low 48 bits of an object value, which hold its pointer.
+const POINTER_MASK: u64 = 0x0000_ffff_ffff_ffff;
+
+/// A value packed into the bits of an `f64`, as in clox. Numbers are stored as themselves, nil
+/// and the booleans are quiet NaNs with a tag in the low bits, and objects are quiet NaNs with
+/// the sign bit set and an `Rc<Obj>` pointer in the low 48 bits.
+///
+/// NOTE: This relies on pointers fitting in 48 bits, which holds for the 64-bit targets this
+///       is built for.
+///
+/// The marker makes a value `!Send` and `!Sync`, as an `Rc<Obj>` is, since the pointer it holds
+/// is one and its count isn't atomic.
+pub struct Value(u64, PhantomData<Rc<Obj>>);
+
+const _: () = assert!(std::mem::size_of::<Value>() == 8);
+
+// Fails to compile if `Value` is `Send` or `Sync`, as the call below then matches more than one
+// impl. This is the check `static_assertions::assert_not_impl_any!` makes.
+const _: fn() = || {
+    trait AmbiguousIfSendOrSync<A> {
+        fn some_item() {}
+    }
+    impl<T: ?Sized> AmbiguousIfSendOrSync<()> for T {}
+
+    struct IfSend;
+    impl<T: ?Sized + Send> AmbiguousIfSendOrSync<IfSend> for T {}
+    struct IfSync;
+    impl<T: ?Sized + Sync> AmbiguousIfSendOrSync<IfSync> for T {}
+
+    let _ = <Value as AmbiguousIfSendOrSync<_>>::some_item;
+};
+
+impl Value {
+    pub const fn number(n: f64) -> Self {
+        // NOTE: A NaN produced by arithmetic could have the bits of a tagged value, so every NaN
+        //       is stored as the one canonical NaN instead.
+        match n.is_nan() {
+            true => Value(f64::NAN.to_bits(), PhantomData),
+            false => Value(n.to_bits(), PhantomData),
+        }
+    }
+
+    pub const fn bool(b: bool) -> Self {
+        match b {
+            true => Value(QNAN | TAG_TRUE, PhantomData),
+            false => Value(QNAN | TAG_FALSE, PhantomData),
+        }
+    }
+
+    pub const fn nil() -> Self {
+        Value(QNAN | TAG_NIL, PhantomData)
+    }
+
+    pub(crate) fn obj(obj: Obj) -> Self {
+        let pointer = Rc::into_raw(Rc::new(obj)) as u64;
+        debug_assert_eq!(
+            pointer & !POINTER_MASK,
+            0,
+            "Object pointer does not fit in 48 bits"
+        );
+
+        Value(SIGN_BIT | QNAN | pointer, PhantomData)
+    }
+
+    pub(crate) fn kind(&self) -> ValueKind<'_> {
+        match self.0 {
+            bits if bits & QNAN != QNAN => ValueKind::Number(f64::from_bits(bits)),
+            bits if bits & SIGN_BIT != 0 => ValueKind::Obj(unsafe { &*self.pointer() }),
+            bits => match bits & !QNAN {
+                TAG_NIL => ValueKind::Nil,
+                TAG_FALSE => ValueKind::Bool(false),
+                TAG_TRUE => ValueKind::Bool(true),
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    pub(crate) fn into_obj(self) -> Option<Obj> {
+        if !self.is_obj() {
+            return None;
+        }
+
+        // NOTE: The reference this value holds is handed over to the `Rc`, so it must not be
+        //       released again when the value is dropped.
+        let value = ManuallyDrop::new(self);
+        let obj = unsafe { Rc::from_raw(value.pointer()) };
+        Some(Rc::try_unwrap(obj).unwrap_or_else(|obj| (*obj).clone()))
+    }
+
+    fn is_obj(&self) -> bool {
+        self.0 & (SIGN_BIT | QNAN) == SIGN_BIT | QNAN
+    }
+
+    fn pointer(&self) -> *const Obj {
+        (self.0 & POINTER_MASK) as *const Obj
+    }
+}
+
+// Objects are reference counted, so copying a value only bumps the count of the object it
+// points to rather than copying the object.
+impl Clone for Value {
+    fn clone(&self) -> Self {
+        if self.is_obj() {
+            unsafe { Rc::increment_strong_count(self.pointer()) };
+        }
+
+        Value(self.0, PhantomData)
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        if self.is_obj() {
+            unsafe { drop(Rc::from_raw(self.pointer())) };
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use super::{Obj, ValueKind};
+
+/// A value as a plain enum. This is the default representation, and the one to reach for when
+/// debugging, as every part of it is visible in a debugger.
+#[derive(Clone)]
+pub struct Value(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Number(f64),
+    Bool(bool),
+    Nil,
+    Obj(Obj),
+}
+
+impl Value {
+    pub const fn number(n: f64) -> Self {
+        Value(Repr::Number(n))
+    }
+
+    pub const fn bool(b: bool) -> Self {
+        Value(Repr::Bool(b))
+    }
+
+    pub const fn nil() -> Self {
+        Value(Repr::Nil)
+    }
+
+    pub(crate) fn obj(obj: Obj) -> Self {
+        Value(Repr::Obj(obj))
+    }
+
+    pub(crate) fn kind(&self) -> ValueKind<'_> {
+        match &self.0 {
+            Repr::Number(n) => ValueKind::Number(*n),
+            Repr::Bool(b) => ValueKind::Bool(*b),
+            Repr::Nil => ValueKind::Nil,
+            Repr::Obj(obj) => ValueKind::Obj(obj),
+        }
+    }
+
+    pub(crate) fn into_obj(self) -> Option<Obj> {
+        match self.0 {
+            Repr::Obj(obj) => Some(obj),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{chunk::Chunk, opcode::Opcode};
+
+pub struct Verifier {}
+
+impl Verifier {
+    /// Checks that a chunk decodes, only refers to constants it has, has a line for all of its
+    /// code and never needs more than `stack_size` values on the stack.
+    pub fn verify(chunk: &Chunk, stack_size: usize) -> Result<(), VerifyError> {
+        if chunk.code.is_empty() {
+            return Err(VerifyError::EmptyChunk);
+        }
+
+        // NOTE: The first run of the line table has to start at offset 0, or the code before it
+        //       has no line to report errors on.
+        if !matches!(chunk.lines.first(), Some((_, 0))) {
+            return Err(VerifyError::MissingLineInfo);
+        }
+
+        // NOTE: There is no control flow yet, so the only path through a chunk is straight down
+        //       from offset 0 until the first `Return`. Anything after that is unreachable and
+        //       only has to decode, it has no stack depth to check.
+        let mut depth = Some(0);
+        let mut offset = 0;
+
+        while offset < chunk.code.len() {
+            let byte = chunk.code[offset];
+            let opcode = match Opcode::from_byte(byte) {
+                Some(opcode) => opcode,
+                None => return Err(VerifyError::UnknownOpcode { offset, byte }),
+            };
+
+            let operand_len = opcode.operand_len();
+            if offset + operand_len >= chunk.code.len() {
+                return Err(VerifyError::TruncatedOperand { offset, opcode });
+            }
+
+            Self::verify_constant(chunk, opcode, offset)?;
+
+            if let Some(current) = depth {
+                let (pops, pushes) = Self::stack_effect(opcode);
+                if current < pops {
+                    return Err(VerifyError::StackUnderflow { offset, opcode });
+                }
+
+                let next = current - pops + pushes;
+                if next > stack_size {
+                    return Err(VerifyError::StackOverflow { offset, opcode });
+                }
+
+                depth = match opcode {
+                    Opcode::Return | Opcode::ReturnNil => None,
+                    _ => Some(next),
+                };
+            }
+
+            offset += 1 + operand_len;
+        }
+
+        // NOTE: The VM doesn't check for running off the end of the code, so every chunk has to
+        //       reach a return.
+        if depth.is_some() {
+            return Err(VerifyError::MissingReturn);
+        }
+
+        Ok(())
+    }
+
+    fn verify_constant(chunk: &Chunk, opcode: Opcode, offset: usize) -> Result<(), VerifyError> {
+        let index = match opcode {
+            Opcode::Constant | Opcode::AddConstant => chunk.code[offset + 1] as usize,
+            Opcode::ConstantLong => u32::from_be_bytes([
+                chunk.code[offset + 1],
+                chunk.code[offset + 2],
+                chunk.code[offset + 3],
+                chunk.code[offset + 4],
+            ]) as usize,
+            _ => return Ok(()),
+        };
+
+        if index >= chunk.constants.len() {
+            return Err(VerifyError::ConstantOutOfRange { offset, index });
+        }
+
+        Ok(())
+    }
+
+    // Returns how many values an instruction pops off the stack, and how many it pushes back.
+    fn stack_effect(opcode: Opcode) -> (usize, usize) {
+        match opcode {
+            Opcode::Return => (1, 0),
+            Opcode::ReturnNil => (0, 0),
+            Opcode::Constant | Opcode::ConstantLong => (0, 1),
+            Opcode::Nil | Opcode::True | Opcode::False => (0, 1),
+            Opcode::Negate | Opcode::Not | Opcode::AddConstant => (1, 1),
+            Opcode::Add
+            | Opcode::Subtract
+            | Opcode::Divide
+            | Opcode::Multiply
+            | Opcode::Equal
+            | Opcode::Greater
+            | Opcode::Less
+            | Opcode::NotEqual
+            | Opcode::GreaterEqual
+            | Opcode::LessEqual => (2, 1),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    EmptyChunk,
+    MissingLineInfo,
+    UnknownOpcode { offset: usize, byte: u8 },
+    TruncatedOperand { offset: usize, opcode: Opcode },
+    ConstantOutOfRange { offset: usize, index: usize },
+    StackUnderflow { offset: usize, opcode: Opcode },
+    StackOverflow { offset: usize, opcode: Opcode },
+    MissingReturn,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::EmptyChunk => write!(f, "Chunk has no code"),
+            VerifyError::MissingLineInfo => write!(f, "Line table has no line for offset 0000"),
+            VerifyError::UnknownOpcode { offset, byte } => {
+                write!(f, "Unknown opcode {} at offset {:04}", byte, offset)
+            }
+            VerifyError::TruncatedOperand { offset, opcode } => {
+                write!(
+                    f,
+                    "Operand of {:?} runs past end of code at offset {:04}",
+                    opcode, offset
+                )
+            }
+            VerifyError::ConstantOutOfRange { offset, index } => {
+                write!(
+                    f,
+                    "Constant {} is out of range at offset {:04}",
+                    index, offset
+                )
+            }
+            VerifyError::StackUnderflow { offset, opcode } => {
+                write!(
+                    f,
+                    "{:?} would underflow the stack at offset {:04}",
+                    opcode, offset
+                )
+            }
+            VerifyError::StackOverflow { offset, opcode } => {
+                write!(
+                    f,
+                    "{:?} would overflow the stack at offset {:04}",
+                    opcode, offset
+                )
+            }
+            VerifyError::MissingReturn => write!(f, "Code runs off the end without a return"),
+        }
+    }
+}
+
+impl Error for VerifyError {}
@@ -1,167 +1,495 @@
-use std::{error::Error, fmt::Display};
+use std::{cmp::Ordering, error::Error, fmt::Display, io::Write};
 
 use crate::{
     chunk::Chunk,
-    compiler::Compiler,
+    compiler,
     dissasembler::Dissasembler,
     opcode::Opcode,
-    value::{Obj, ObjType, Value},
+    optimizer::Optimizations,
+    value::{ErrorObj, Obj, ObjType, Value},
+    verifier::{Verifier, VerifyError},
 };
 
-const STACK_MAX: usize = 256;
+/// The number of values that fit on the stack, unless the VM is built with another size.
+pub const STACK_MAX: usize = 256;
 
 macro_rules! binary_op {
     ($self:ident, $op:tt, $value_type:ident) => {
         {
             if !$self.peek(0).is_number() || !$self.peek(1).is_number() {
-                $self.runtime_error("Operands must be numbers");
-                return Err(InterpretError::RuntimeError);
+                return Err("Operands must be numbers");
             }
 
             let b = $self.pop();
             let a = $self.pop();
             let result = Value::$value_type(a.as_f64().unwrap() $op b.as_f64().unwrap());
             $self.push(result);
+            Ok(ExecutionState::Running)
         }
-    }
+    };
+    // Pushes whether the operands do not compare as `$ordering`, which is true when either is NaN.
+    ($self:ident, not $ordering:ident) => {
+        {
+            if !$self.peek(0).is_number() || !$self.peek(1).is_number() {
+                return Err("Operands must be numbers");
+            }
+
+            let b = $self.pop().as_f64().unwrap();
+            let a = $self.pop().as_f64().unwrap();
+            let result = Value::bool(a.partial_cmp(&b) != Some(Ordering::$ordering));
+            $self.push(result);
+            Ok(ExecutionState::Running)
+        }
+    };
 }
 
+// What running one instruction did. The error is the message of the runtime error it raised.
+type Step = Result<ExecutionState, &'static str>;
+
+// Runs the instruction whose opcode has just been read. `ip` starts just past the opcode and is
+// left at the next instruction.
+type Handler = fn(&mut Vm, &Chunk, &mut usize) -> Step;
+
+// NOTE: Indexed by opcode byte. Chunks are verified before they run, so the bytes that are not
+//       opcodes never reach the table.
+static HANDLERS: [Handler; 256] = Vm::handlers();
+
 pub struct Vm {
     chunk: Option<Chunk>,
     ip: usize,
-    stack: [Value; STACK_MAX],
+    // Set once the chunk has returned or raised a runtime error, as the code after either isn't
+    // meant to be run.
+    finished: bool,
+    stack: Vec<Value>,
     stack_top: usize,
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    trace_output: Box<dyn Write>,
+    trace: bool,
+    dump: bool,
+    optimizations: Optimizations,
+    typecheck: bool,
 }
 
 type InterpretResult = Result<(), InterpretError>;
 
 impl Vm {
     pub fn new() -> Self {
-        const value: Value = Value::Nil;
-        Vm {
-            chunk: None,
-            ip: 0,
-            stack: [value; STACK_MAX],
-            stack_top: 0,
-        }
+        VmBuilder::new().build()
+    }
+
+    pub fn builder() -> VmBuilder {
+        VmBuilder::new()
+    }
+
+    /// Sets where program output is written, which is stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Sets where compile and runtime errors are written, which is stderr by default.
+    pub fn set_error_output(&mut self, output: Box<dyn Write>) {
+        self.error_output = output;
+    }
+
+    /// Sets where the `DEBUG` execution trace and `DUMP` listing are written, which is stderr by
+    /// default so that they don't interleave with program output.
+    pub fn set_trace_output(&mut self, output: Box<dyn Write>) {
+        self.trace_output = output;
+    }
+
+    /// Sets which optimizations source given to `interpret` and `evaluate` is compiled with,
+    /// which is all of them by default.
+    pub fn set_optimizations(&mut self, optimizations: Optimizations) {
+        self.optimizations = optimizations;
+    }
+
+    pub fn optimizations(&self) -> Optimizations {
+        self.optimizations
+    }
+
+    /// Whether source is type checked before it is compiled.
+    pub fn typecheck(&self) -> bool {
+        self.typecheck
     }
 
     pub fn interpret(&mut self, source: &str) -> InterpretResult {
-        self.ip = 0;
-        let mut compiler = Compiler::new(source);
-        let chunk = compiler.compile()?;
-        self.chunk = Some(chunk);
+        let chunk = self.compile(source)?;
+
+        self.interpret_chunk(chunk)
+    }
+
+    /// Compiles source the way `interpret` would, writing any errors to the error output, but
+    /// hands back the chunk instead of running it.
+    pub fn compile(&mut self, source: &str) -> Result<Chunk, InterpretError> {
+        let chunk = compiler::compile(
+            source,
+            self.optimizations,
+            self.typecheck,
+            &mut self.error_output,
+        )?;
+        if self.dump {
+            writeln!(self.trace_output, "{:?}", chunk).expect("Could not write listing");
+        }
+
+        Ok(chunk)
+    }
 
+    pub fn interpret_chunk(&mut self, chunk: Chunk) -> InterpretResult {
+        self.load(chunk)?;
         self.run()?;
 
         Ok(())
     }
 
-    fn run(&mut self) -> InterpretResult {
+    /// Throws away the loaded chunk and everything on the stack, keeping the VM's settings.
+    pub fn reset(&mut self) {
+        self.chunk = None;
+        self.ip = 0;
+        self.finished = false;
+        self.reset_stack();
+    }
+
+    /// Verifies a chunk and gets it ready to run, without executing any of it. Execution can
+    /// then be driven one instruction at a time with `step`.
+    pub fn load(&mut self, chunk: Chunk) -> InterpretResult {
+        Verifier::verify(&chunk, self.stack.len()).map_err(InterpretError::InvalidBytecode)?;
+
+        self.ip = 0;
+        self.chunk = Some(chunk);
+        self.finished = false;
+
+        Ok(())
+    }
+
+    /// Compiles and runs an expression on top of the current state, and hands back its value
+    /// rather than printing it. The chunk, ip and stack are left as they were, even on error,
+    /// so this is safe to call while stopped part way through a chunk.
+    pub fn evaluate(&mut self, source: &str) -> Result<Value, InterpretError> {
+        let chunk = self.compile(source)?;
+        // NOTE: The expression runs on top of whatever is already on the stack.
+        let stack_size = self.stack.len() - self.stack_top;
+        Verifier::verify(&chunk, stack_size).map_err(InterpretError::InvalidBytecode)?;
+
+        let saved_chunk = self.chunk.replace(chunk);
+        let saved_ip = self.ip;
+        let saved_finished = self.finished;
+        let saved_stack = self.stack[..self.stack_top].to_vec();
+        self.ip = 0;
+        self.finished = false;
+
+        let result = self.run_to_return();
+
+        self.chunk = saved_chunk;
+        self.ip = saved_ip;
+        self.finished = saved_finished;
+        self.stack_top = saved_stack.len();
+        for (slot, value) in saved_stack.into_iter().enumerate() {
+            self.stack[slot] = value;
+        }
+
+        result
+    }
+
+    fn run_to_return(&mut self) -> Result<Value, InterpretError> {
         loop {
-            if std::env::var("DEBUG").is_ok() {
-                print!("          ");
-                for value in self.stack.iter().take(self.stack_top) {
-                    print!("[ {} ]", value);
-                }
-                println!();
-
-                // NOTE: Cloning the IP pointer here prevents the disassembler from moving the offset
-                //       forward, which would cause the VM to skip instructions.
-                Dissasembler::trace_instruction(self.chunk.as_ref().unwrap(), &mut self.ip.clone());
+            let code = &self.chunk.as_ref().unwrap().code;
+            if code[self.ip] == Opcode::Return as u8 {
+                return Ok(self.pop());
+            }
+            if code[self.ip] == Opcode::ReturnNil as u8 {
+                return Ok(Value::nil());
             }
 
-            let opcode = self.read_opcode()?;
-
-            match opcode {
-                Opcode::Return => {
-                    println!("{}", self.pop());
-                    return Ok(());
-                }
-                Opcode::Constant => {
-                    let constant = self.read_constant()?.clone();
-                    self.push(constant);
-                }
-                Opcode::Nil => {
-                    self.push(Value::Nil);
-                }
-                Opcode::True => {
-                    self.push(Value::Bool(true));
-                }
-                Opcode::False => {
-                    self.push(Value::Bool(false));
-                }
-                Opcode::Equal => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let result = Value::Bool(a == b);
-                    self.push(result);
-                }
-                Opcode::Greater => binary_op!(self, >, Bool),
-                Opcode::Less => binary_op!(self, <, Bool),
-                Opcode::Negate => {
-                    if !self.peek(0).is_number() {
-                        self.runtime_error("Operand must be a number");
-                        return Err(InterpretError::RuntimeError);
-                    }
-
-                    let negated_value = Value::Number(-(self.pop().as_f64().unwrap()));
-                    self.push(negated_value);
-                }
-                Opcode::Add => {
-                    if self.peek(0).is_obj_type(ObjType::String)
-                        && self.peek(1).is_obj_type(ObjType::String)
-                    {
-                        self.concatenate();
-                    } else if self.peek(0).is_number() && self.peek(1).is_number() {
-                        let a = self.pop();
-                        let b = self.pop();
-                        let result = Value::Number(a.as_f64().unwrap() + b.as_f64().unwrap());
-                        self.push(result);
-                    } else {
-                        self.runtime_error("Operands must be two numbers or two strings");
-                        return Err(InterpretError::RuntimeError);
-                    }
-                }
-                Opcode::Subtract => binary_op!(self, -, Number),
-                Opcode::Divide => binary_op!(self, /, Number),
-                Opcode::Multiply => binary_op!(self, *, Number),
-                Opcode::Not => {
-                    let result = Value::Bool(self.pop().is_falsey());
-                    self.push(result);
-                }
-                _ => return Err(InterpretError::CompileError),
+            if let ExecutionState::Finished = self.step()? {
+                return Ok(Value::nil());
             }
+        }
+    }
+
+    /// Runs the loaded chunk from `ip` to the end. Once it has finished, or with no chunk loaded,
+    /// there is nothing to run and this returns straight away.
+    pub fn run(&mut self) -> InterpretResult {
+        if !self.trace {
+            return self.execute(false).map(|_| ());
+        }
+
+        while self.can_step() {
+            self.trace().expect("Could not write trace");
 
-            if self.ip >= self.chunk.as_ref().unwrap().code.len() {
-                return Ok(());
+            if let ExecutionState::Finished = self.step()? {
+                break;
             }
         }
+
+        Ok(())
+    }
+
+    /// Executes the single instruction at `ip`, or returns `Finished` if there is none to run.
+    pub fn step(&mut self) -> Result<ExecutionState, InterpretError> {
+        self.execute(true)
+    }
+
+    // Whether there is an instruction at `ip` to run.
+    fn can_step(&self) -> bool {
+        match &self.chunk {
+            Some(chunk) => !self.finished && self.ip < chunk.code.len(),
+            None => false,
+        }
+    }
+
+    // Runs instructions until one finishes the chunk, or only the one at `ip` when `single` is
+    // set. The chunk is moved out while it runs so that it and the ip can be held in locals.
+    //
+    // NOTE: There is no check for running off the end of the code within the loop, as the
+    //       verifier only passes chunks that reach a return, and nothing runs after one.
+    fn execute(&mut self, single: bool) -> Result<ExecutionState, InterpretError> {
+        if !self.can_step() {
+            return Ok(ExecutionState::Finished);
+        }
+
+        let chunk = self.chunk.take().unwrap();
+        let mut ip = self.ip;
+
+        let result = loop {
+            let opcode = chunk.code[ip];
+            ip += 1;
+
+            match HANDLERS[opcode as usize](self, &chunk, &mut ip) {
+                Ok(ExecutionState::Running) if !single => {}
+                result => break result,
+            }
+        };
+
+        self.chunk = Some(chunk);
+        self.ip = ip;
+        self.finished = !matches!(result, Ok(ExecutionState::Running));
+        result.map_err(|message| self.runtime_error(message))
+    }
+
+    const fn handlers() -> [Handler; 256] {
+        let mut handlers: [Handler; 256] = [Vm::op_unknown; 256];
+
+        let mut byte = 0;
+        while byte < handlers.len() {
+            if let Some(opcode) = Opcode::from_byte(byte as u8) {
+                handlers[byte] = Vm::handler(opcode);
+            }
+            byte += 1;
+        }
+
+        handlers
+    }
+
+    const fn handler(opcode: Opcode) -> Handler {
+        match opcode {
+            Opcode::Return => Vm::op_return,
+            Opcode::ReturnNil => Vm::op_return_nil,
+            Opcode::Constant => Vm::op_constant,
+            Opcode::ConstantLong => Vm::op_constant_long,
+            Opcode::Nil => Vm::op_nil,
+            Opcode::True => Vm::op_true,
+            Opcode::False => Vm::op_false,
+            Opcode::Equal => Vm::op_equal,
+            Opcode::NotEqual => Vm::op_not_equal,
+            Opcode::Greater => Vm::op_greater,
+            Opcode::Less => Vm::op_less,
+            Opcode::GreaterEqual => Vm::op_greater_equal,
+            Opcode::LessEqual => Vm::op_less_equal,
+            Opcode::Negate => Vm::op_negate,
+            Opcode::Add => Vm::op_add,
+            Opcode::AddConstant => Vm::op_add_constant,
+            Opcode::Subtract => Vm::op_subtract,
+            Opcode::Divide => Vm::op_divide,
+            Opcode::Multiply => Vm::op_multiply,
+            Opcode::Not => Vm::op_not,
+        }
+    }
+
+    fn op_unknown(&mut self, chunk: &Chunk, ip: &mut usize) -> Step {
+        unreachable!("Unknown opcode {} in a verified chunk", chunk.code[*ip - 1])
+    }
+
+    fn op_return(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        let value = self.pop();
+        writeln!(self.output, "{}", value).expect("Could not write output");
+        Ok(ExecutionState::Finished)
+    }
+
+    fn op_return_nil(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        writeln!(self.output, "{}", Value::nil()).expect("Could not write output");
+        Ok(ExecutionState::Finished)
+    }
+
+    fn op_constant(&mut self, chunk: &Chunk, ip: &mut usize) -> Step {
+        let constant = Self::read_constant(chunk, ip).clone();
+        self.push(constant);
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_constant_long(&mut self, chunk: &Chunk, ip: &mut usize) -> Step {
+        let constant = Self::read_constant_long(chunk, ip).clone();
+        self.push(constant);
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_nil(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        self.push(Value::nil());
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_true(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        self.push(Value::bool(true));
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_false(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        self.push(Value::bool(false));
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_equal(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        let b = self.pop();
+        let a = self.pop();
+        self.push(Value::bool(a == b));
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_not_equal(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        let b = self.pop();
+        let a = self.pop();
+        self.push(Value::bool(!(a == b)));
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_greater(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, >, bool)
+    }
+
+    fn op_less(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, <, bool)
+    }
+
+    // NOTE: These behave exactly as the `Less, Not` and `Greater, Not` pairs they replace, so
+    //       comparing with NaN gives true.
+    fn op_greater_equal(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, not Less)
+    }
+
+    fn op_less_equal(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, not Greater)
+    }
+
+    fn op_negate(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        if !self.peek(0).is_number() {
+            return Err("Operand must be a number");
+        }
+
+        let negated_value = Value::number(-(self.pop().as_f64().unwrap()));
+        self.push(negated_value);
+        Ok(ExecutionState::Running)
+    }
+
+    fn op_add(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        self.add()
+    }
+
+    // NOTE: The same as `Constant` followed by `Add`, errors included.
+    fn op_add_constant(&mut self, chunk: &Chunk, ip: &mut usize) -> Step {
+        let constant = Self::read_constant(chunk, ip).clone();
+        self.push(constant);
+        self.add()
+    }
+
+    fn op_subtract(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, -, number)
+    }
+
+    fn op_divide(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, /, number)
+    }
+
+    fn op_multiply(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        binary_op!(self, *, number)
+    }
+
+    fn op_not(&mut self, _: &Chunk, _: &mut usize) -> Step {
+        let result = Value::bool(self.pop().is_falsey());
+        self.push(result);
+        Ok(ExecutionState::Running)
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn chunk(&self) -> Option<&Chunk> {
+        self.chunk.as_ref()
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack[..self.stack_top]
+    }
+
+    fn trace(&mut self) -> std::io::Result<()> {
+        write!(self.trace_output, "          ")?;
+        for value in self.stack.iter().take(self.stack_top) {
+            write!(self.trace_output, "[ {} ]", value)?;
+        }
+        writeln!(self.trace_output)?;
+
+        // NOTE: Cloning the IP pointer here prevents the disassembler from moving the offset
+        //       forward, which would cause the VM to skip instructions.
+        Dissasembler::trace_instruction(
+            self.chunk.as_ref().unwrap(),
+            &mut self.ip.clone(),
+            &mut self.trace_output,
+        )
+    }
+
+    fn add(&mut self) -> Step {
+        if self.peek(0).is_obj_type(ObjType::String) && self.peek(1).is_obj_type(ObjType::String) {
+            self.concatenate();
+        } else if self.peek(0).is_number() && self.peek(1).is_number() {
+            let a = self.pop();
+            let b = self.pop();
+            let result = Value::number(a.as_f64().unwrap() + b.as_f64().unwrap());
+            self.push(result);
+        } else {
+            return Err("Operands must be two numbers or two strings");
+        }
+
+        Ok(ExecutionState::Running)
     }
 
     fn concatenate(&mut self) {
         let b = self.pop();
         let a = self.pop();
 
-        let mut a = a.as_string().unwrap();
-        let b = b.as_string().unwrap();
+        let mut a = a.into_string().unwrap();
+        let b = b.into_string().unwrap();
 
         a.push_str(&b);
 
-        let result = Value::Obj(Obj::String(a));
+        let result = Value::obj(Obj::String(a));
         self.push(result);
     }
 
-    fn read_opcode(&mut self) -> Result<Opcode, InterpretError> {
-        let opcode = self.chunk.as_ref().unwrap().code[self.ip];
-        self.ip += 1;
-        Ok(opcode.into())
+    fn read_constant<'c>(chunk: &'c Chunk, ip: &mut usize) -> &'c Value {
+        let constant = chunk.code[*ip];
+        *ip += 1;
+        &chunk.constants[constant as usize]
     }
 
-    fn read_constant(&mut self) -> Result<&Value, InterpretError> {
-        let constant = (self.chunk.as_ref().unwrap()).code[self.ip];
-        self.ip += 1;
-        Ok(&self.chunk.as_ref().unwrap().constants[constant as usize])
+    fn read_constant_long<'c>(chunk: &'c Chunk, ip: &mut usize) -> &'c Value {
+        let constant = u32::from_be_bytes([
+            chunk.code[*ip],
+            chunk.code[*ip + 1],
+            chunk.code[*ip + 2],
+            chunk.code[*ip + 3],
+        ]);
+        *ip += 4;
+        &chunk.constants[constant as usize]
     }
 
     fn push(&mut self, value: Value) {
@@ -169,9 +497,10 @@ impl Vm {
         self.stack_top += 1;
     }
 
+    // NOTE: The slot is left holding nil rather than a copy, so popping never clones a string.
     fn pop(&mut self) -> Value {
         self.stack_top -= 1;
-        self.stack[self.stack_top].clone()
+        std::mem::replace(&mut self.stack[self.stack_top], Value::nil())
     }
 
     fn peek(&self, distance: usize) -> &Value {
@@ -182,24 +511,147 @@ impl Vm {
         self.stack_top = 0;
     }
 
-    fn runtime_error(&mut self, message: &str) {
+    fn runtime_error(&mut self, message: &str) -> InterpretError {
         let line = self.chunk.as_ref().unwrap().line_for_instruction_n(self.ip);
-        eprintln!("[line {}] Error: {}", line, message);
+        writeln!(self.error_output, "[line {}] Error: {}", line, message)
+            .expect("Could not write runtime error");
+
+        // NOTE: There is only ever the one top level script running, so the trace is a single
+        //       frame for now.
+        let trace = vec![format!("[line {}] in script", line)];
+        let error = ErrorObj::new(message, line, trace);
+
         self.reset_stack();
+        InterpretError::RuntimeError(Value::obj(Obj::Error(Box::new(error))))
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
     }
 }
 
+/// Configures a `Vm` before it is built. Every option has the same default as `Vm::new`.
+pub struct VmBuilder {
+    stack_size: usize,
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    trace_output: Box<dyn Write>,
+    trace: bool,
+    dump: bool,
+    optimizations: Optimizations,
+    typecheck: bool,
+}
+
+impl VmBuilder {
+    pub fn new() -> Self {
+        VmBuilder {
+            stack_size: STACK_MAX,
+            output: Box::new(std::io::stdout()),
+            error_output: Box::new(std::io::stderr()),
+            trace_output: Box::new(std::io::stderr()),
+            trace: std::env::var("DEBUG").is_ok(),
+            dump: std::env::var("DUMP").is_ok(),
+            optimizations: Optimizations::all(),
+            typecheck: false,
+        }
+    }
+
+    /// How many values fit on the stack. Chunks that could need more are rejected when loaded.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Where program output is written, stdout by default.
+    pub fn output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Where compile and runtime errors are written, stderr by default.
+    pub fn error_output(mut self, output: Box<dyn Write>) -> Self {
+        self.error_output = output;
+        self
+    }
+
+    /// Whether every instruction is traced as it runs. The default is whether the `DEBUG`
+    /// environment variable is set.
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Where the trace is written, stderr by default.
+    pub fn trace_output(mut self, output: Box<dyn Write>) -> Self {
+        self.trace_output = output;
+        self
+    }
+
+    /// Whether the disassembly of each chunk compiled from source is written to the trace
+    /// output. The default is whether the `DUMP` environment variable is set.
+    pub fn dump(mut self, dump: bool) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    /// Which optimizations source is compiled with, all of them by default.
+    pub fn optimizations(mut self, optimizations: Optimizations) -> Self {
+        self.optimizations = optimizations;
+        self
+    }
+
+    /// Whether source is type checked before it is compiled, reporting operations that are
+    /// bound to fail as compile errors. Off by default.
+    pub fn typecheck(mut self, typecheck: bool) -> Self {
+        self.typecheck = typecheck;
+        self
+    }
+
+    pub fn build(self) -> Vm {
+        Vm {
+            chunk: None,
+            ip: 0,
+            finished: false,
+            stack: vec![Value::nil(); self.stack_size],
+            stack_top: 0,
+            output: self.output,
+            error_output: self.error_output,
+            trace_output: self.trace_output,
+            trace: self.trace,
+            dump: self.dump,
+            optimizations: self.optimizations,
+            typecheck: self.typecheck,
+        }
+    }
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        VmBuilder::new()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExecutionState {
+    Running,
+    Finished,
+}
+
 #[derive(Debug)]
 pub enum InterpretError {
     CompileError,
-    RuntimeError,
+    RuntimeError(Value),
+    InvalidBytecode(VerifyError),
 }
 
 impl Display for InterpretError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InterpretError::CompileError => write!(f, "Compile error"),
-            InterpretError::RuntimeError => write!(f, "Runtime error"),
+            InterpretError::RuntimeError(error) => write!(f, "Runtime error: {}", error),
+            InterpretError::InvalidBytecode(error) => write!(f, "Invalid bytecode: {}", error),
         }
     }
 }
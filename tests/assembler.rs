@@ -0,0 +1,117 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file};
+
+fn run(name: &str, listing: &str) -> Output {
+    let path = temp_file(name, listing.as_bytes());
+    let output = lox().arg(&path).output().expect("Could not run program");
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+// Assembles a listing that has to be rejected, and returns the reason.
+fn assemble_error(name: &str, listing: &str) -> String {
+    let output = run(name, listing);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let (_, error) = stderr
+        .split_once("': ")
+        .unwrap_or_else(|| panic!("Unexpected error: {}", stderr));
+    error.trim_end().to_string()
+}
+
+// The DUMP listing of a string with a newline, a quote and a backslash in it assembles back to
+// the same string.
+#[test]
+fn assembles_the_listing_of_strings_that_need_escapes() {
+    let source = temp_file("escapes.lox", "\"it's a\nb \\ c\"".as_bytes());
+    let output = lox()
+        .arg(&source)
+        .env("DUMP", "1")
+        .output()
+        .expect("Could not run program");
+    std::fs::remove_file(&source).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let listing = String::from_utf8(output.stderr).unwrap();
+    assert!(listing.contains(r#"'"it\'s a\nb \\ c"'"#), "{}", listing);
+
+    let assembled = run("escapes.loxasm", &listing);
+    assert!(assembled.status.success(), "{:?}", assembled);
+    assert_eq!(assembled.stdout, output.stdout);
+    assert_eq!(assembled.stdout, b"it's a\nb \\ c\n");
+}
+
+#[test]
+fn assembles_escapes_in_constant_directives() {
+    let listing = "\
+.constant 0 \"tab\\there; \\\"quoted\\\"\" ; a comment after a quote
+Constant 0
+Return
+";
+    let output = run("directive.loxasm", listing);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"tab\there; \"quoted\"\n");
+}
+
+#[test]
+fn assembles_constant_long() {
+    let listing = "\
+== main ==
+0000    1 ConstantLong        0 '1.5'
+0005    | ConstantLong        1 '\"a\"'
+0010    2 Return
+";
+    let output = run("constant-long.loxasm", listing);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"a\n");
+}
+
+#[test]
+fn assembles_less() {
+    let listing = "\
+Constant 0 '1'
+Constant 1 '2'
+Less
+Return
+";
+    let output = run("less.loxasm", listing);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"true\n");
+}
+
+#[test]
+fn reports_an_unknown_instruction() {
+    assert_eq!(
+        assemble_error("unknown.loxasm", "Nil\nPush 1\nReturn\n"),
+        "[line 2] Error: Unknown instruction 'Push'"
+    );
+}
+
+#[test]
+fn reports_operands_on_an_instruction_without_any() {
+    assert_eq!(
+        assemble_error("operands.loxasm", "Nil 1\nReturn\n"),
+        "[line 1] Error: Nil does not take operands"
+    );
+}
+
+#[test]
+fn reports_an_undefined_constant() {
+    assert_eq!(
+        assemble_error("undefined.loxasm", ".constant 0 1\nConstant 3\nReturn\n"),
+        "[line 2] Error: Constant 3 is not defined"
+    );
+}
+
+#[test]
+fn reports_an_invalid_escape() {
+    assert_eq!(
+        assemble_error("escape.loxasm", ".constant 0 \"a\\qb\"\n"),
+        "[line 1] Error: Invalid escape '\\q' in constant"
+    );
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file, temp_path};
+
+// Programs that cover every kind of expression, operands spread over several lines, and enough
+// constants to need `ConstantLong`.
+fn programs() -> Vec<String> {
+    let mut programs: Vec<String> = [
+        "1",
+        "1 + 2 * 3 - 4 / 5\n",
+        "-(1 + 2)\n* 3\n\n== 9\n",
+        "!true == !!false",
+        "\"a\" + \"b\nc\"\n",
+        "nil != nil",
+        "1 < 2 == (3 >= 4) != (5 <= 6)\n\n\n",
+        "(\n1\n+\n2\n)\n> -\n3",
+        "// a comment\n1.5 + 2 // another\n",
+        "--1",
+    ]
+    .iter()
+    .map(|program| program.to_string())
+    .collect();
+
+    let terms: Vec<String> = (0..300).map(|n| n.to_string()).collect();
+    programs.push(terms.join(" +\n"));
+
+    programs
+}
+
+// Compiles with the single-pass compiler, or through the AST without constant folding.
+fn compile(source: &str, name: &str, single_pass: bool) -> (Output, Option<Vec<u8>>) {
+    let path = temp_file(&format!("{}.lox", name), source.as_bytes());
+    let output = temp_path(&format!("{}-{}.loxb", name, single_pass));
+
+    let mut command = lox();
+    command
+        .arg("compile")
+        .arg(&path)
+        .arg("--output")
+        .arg(&output);
+    match single_pass {
+        true => command.arg("--single-pass"),
+        false => command.arg("--no-opt"),
+    };
+
+    let result = command.output().expect("Could not run compiler");
+    let compiled = std::fs::read(&output).ok();
+    std::fs::remove_file(&path).unwrap();
+    let _ = std::fs::remove_file(&output);
+    (result, compiled)
+}
+
+#[test]
+fn ast_front_end_matches_single_pass_compiler() {
+    for (n, program) in programs().iter().enumerate() {
+        let name = format!("ast-{}", n);
+        let (single_pass, expected) = compile(program, &name, true);
+        let (ast, actual) = compile(program, &name, false);
+
+        assert!(single_pass.status.success(), "{:?}", program);
+        assert!(ast.status.success(), "{:?}", program);
+        assert_eq!(expected, actual, "{:?}", program);
+    }
+}
+
+#[test]
+fn ast_front_end_reports_the_same_errors() {
+    let programs = ["1 +", "(1", "1 2", ")", "1 + @", "\"unterminated", "* 2 -"];
+
+    for (n, program) in programs.iter().enumerate() {
+        let name = format!("ast-error-{}", n);
+        let (single_pass, _) = compile(program, &name, true);
+        let (ast, _) = compile(program, &name, false);
+
+        let errors = |output: &Output| -> Vec<String> {
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .filter(|line| line.starts_with("[line"))
+                .map(|line| line.to_string())
+                .collect()
+        };
+
+        assert!(!single_pass.status.success(), "{:?}", program);
+        assert!(!ast.status.success(), "{:?}", program);
+        assert!(!errors(&single_pass).is_empty(), "{:?}", program);
+        assert_eq!(errors(&single_pass), errors(&ast), "{:?}", program);
+    }
+}
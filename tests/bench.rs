@@ -0,0 +1,93 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file, temp_path};
+
+fn bench(args: &[&str]) -> Output {
+    lox()
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("bench")
+        .args(args)
+        .output()
+        .expect("Could not run program")
+}
+
+// Every program in the bench directory runs, and the saved results can be compared against.
+#[test]
+fn saves_and_compares_against_a_baseline() {
+    let path = temp_path("bench.json");
+    let results = path.to_str().unwrap();
+
+    let output = bench(&["--runs", "2", "--save", results]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(results).unwrap()).unwrap();
+    let benchmarks = json["benchmarks"].as_array().unwrap();
+    let programs = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/bench")).unwrap();
+    assert_eq!(benchmarks.len(), programs.count());
+    for benchmark in benchmarks {
+        assert_eq!(benchmark["runs"], 2);
+        assert!(benchmark["instructions"].as_u64().unwrap() > 0);
+        assert!(benchmark["mean"].as_f64().unwrap() > 0.0);
+    }
+
+    let output = bench(&["--runs", "2", "--baseline", results, "bench/stack.lox"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.starts_with("stack "), "{}", stdout);
+    assert!(stdout.contains("% vs baseline"), "{}", stdout);
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Folding is always off, so each operation in the source is one instruction.
+#[test]
+fn counts_every_instruction() {
+    let path = temp_file("count.lox", b"(1 + 2) * -3");
+    let results = temp_path("count.json");
+
+    let output = bench(&[
+        "--runs",
+        "1",
+        "--no-superinstructions",
+        "--save",
+        results.to_str().unwrap(),
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&results).unwrap()).unwrap();
+    assert_eq!(json["benchmarks"][0]["instructions"], 7);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&results).unwrap();
+}
+
+// Each superinstruction is one dispatch in place of two: `Constant, Add` becomes `AddConstant`,
+// and `Nil, Return` becomes `ReturnNil`.
+#[test]
+fn superinstructions_save_dispatches() {
+    let path = temp_path("fused.lox");
+    let results = temp_path("fused.json");
+
+    let count = |source: &str, flags: &[&str]| {
+        std::fs::write(&path, source).unwrap();
+        let mut args = vec!["--runs", "1", "--save", results.to_str().unwrap()];
+        args.extend_from_slice(flags);
+        args.push(path.to_str().unwrap());
+
+        let output = bench(&args);
+        assert!(output.status.success(), "{:?}", output);
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&results).unwrap()).unwrap();
+        json["benchmarks"][0]["instructions"].as_u64().unwrap()
+    };
+
+    assert_eq!(count("1 + 2 + 3 == nil", &["--no-superinstructions"]), 8);
+    assert_eq!(count("1 + 2 + 3 == nil", &[]), 6);
+    assert_eq!(count("nil", &["--no-superinstructions"]), 2);
+    assert_eq!(count("nil", &[]), 1);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&results).unwrap();
+}
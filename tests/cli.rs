@@ -0,0 +1,229 @@
+mod common;
+
+use std::{
+    io::Write,
+    process::{Output, Stdio},
+};
+
+use common::{lox, temp_file, temp_path};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = lox()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run program");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().expect("Could not run program")
+}
+
+#[test]
+fn runs_source_from_the_command_line() {
+    let output = run(&["-e", "1 + 2"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"3\n");
+
+    let output = run(&["-e", "-1"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"-1\n");
+}
+
+#[test]
+fn runs_source_from_stdin() {
+    let output = run(&["-"], "\"a\" + \"b\"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"ab\n");
+}
+
+// Offsets into the source count bytes, so text after a multi-byte character scans the same.
+#[test]
+fn runs_source_with_non_ascii_text() {
+    let output = run(&["-e", "// café ☕\n\"naïve \" + \"😀\""], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "naïve 😀\n");
+
+    let output = run(&["-e", "\"é\" + ☕"], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error: Unexpected character: ☕\n[line 1] Error at end: Expect expression.\n"
+    );
+}
+
+// Exit codes follow sysexits.h, as clox's do, and errors are reported without a panic.
+#[test]
+fn exits_with_the_code_for_each_kind_of_error() {
+    let output = run(&["-e", "1 +"], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at end: Expect expression.\n"
+    );
+
+    let output = run(&["-"], "-nil");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error: Operand must be a number\n"
+    );
+
+    let missing = temp_path("missing.lox");
+    let output = run(&[missing.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(74));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Could not load '"));
+
+    let output = run(&["--no-such-flag"], "");
+    assert_eq!(output.status.code(), Some(64));
+
+    let output = run(&["-e", "1", "script.lox"], "");
+    assert_eq!(output.status.code(), Some(64));
+}
+
+// The subcommands that never compile Lox source to run reject the flags that say how to.
+#[test]
+fn rejects_compile_flags_where_they_mean_nothing() {
+    // NOTE: fmt and lint are given a path, so that a missing one isn't what gets reported.
+    for (command, paths) in [
+        ("dap", &[][..]),
+        ("fmt", &["a.lox"]),
+        ("lint", &["a.lox"]),
+        ("lsp", &[]),
+    ] {
+        for flag in [
+            "--no-opt",
+            "--no-fold",
+            "--no-superinstructions",
+            "--typecheck",
+        ] {
+            let output = run(&[&[command, flag][..], paths].concat(), "");
+            assert_eq!(output.status.code(), Some(64), "{} {}", command, flag);
+            assert_eq!(
+                String::from_utf8_lossy(&output.stderr),
+                format!("error: {} can't be used with {}\n", flag, command)
+            );
+        }
+    }
+}
+
+#[test]
+fn lints_comparisons_with_a_constant_result() {
+    let path = temp_file("lint.lox", b"1 < 2\n== (-(3) == -3)\n== (1 < 3 + nil)\n");
+    let output = run(&["lint", path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "{0}: [line 1] Warning at '<': '<' compares two literals, so it always has the same \
+             result. (constant-comparison)\n\
+             {0}: [line 2] Warning at '==': Both sides of '==' are the same, so it always has the \
+             same result. (constant-comparison)\n",
+            path.display()
+        )
+    );
+
+    let path = temp_file("lint-clean.lox", b"1 + 2\n");
+    let output = run(&["lint", path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+// Linting nothing is a mistake in how lint was run, rather than a clean bill of health.
+#[test]
+fn lint_needs_a_path() {
+    let output = run(&["lint"], "");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(output.stdout.is_empty());
+}
+
+// An allow comment silences a rule on its own line and the one after it, but no further.
+#[test]
+fn allows_lints_with_an_inline_comment() {
+    let source = "\
+1 < 2
+// lox-allow(constant-comparison)
+== (nil == nil)
+== (true != true) // lox-allow(unknown-rule, constant-comparison)
+
+== (3 >= 3)
+";
+    let path = temp_file("lint-allow.lox", source.as_bytes());
+    let output = run(&["lint", path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split_once(": ").unwrap().1)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "[line 1] Warning at '<': '<' compares two literals, so it always has the same \
+             result. (constant-comparison)",
+            "[line 6] Warning at '>=': Both sides of '>=' are the same, so it always has the \
+             same result. (constant-comparison)",
+        ]
+    );
+}
+
+#[test]
+fn reads_rule_settings_from_the_lint_config() {
+    let source = temp_file("lint-config.lox", b"1 < 2\n");
+    let lint = |config: &str| {
+        let path = temp_file("loxlint", config.as_bytes());
+        let output = run(
+            &[
+                "lint",
+                "--config",
+                path.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ],
+            "",
+        );
+        std::fs::remove_file(&path).unwrap();
+        output
+    };
+
+    let output = lint("# Every rule is on by default.\nconstant-comparison = off # noisy\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = lint("constant-comparison = on\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = lint("no-such-rule = off\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Could not parse lint config: [line 1] Error in lint config: Unknown rule 'no-such-rule'\n"
+    );
+
+    let output = lint("\nconstant-comparison = maybe\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Could not parse lint config: [line 2] Error in lint config: Expected 'on' or 'off', \
+         found 'maybe'\n"
+    );
+
+    let output = lint("constant-comparison off\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Could not parse lint config: [line 1] Error in lint config: Expected '<rule> = on|off', \
+         found 'constant-comparison off'\n"
+    );
+
+    std::fs::remove_file(&source).unwrap();
+}
@@ -0,0 +1,54 @@
+// NOTE: Each test file builds its own copy of this module and only uses some of it.
+#![allow(dead_code)]
+
+use std::{path::PathBuf, process::Command};
+
+use crafting_interpreters_vm::serializer::crc32;
+
+/// The interpreter binary, ready for arguments.
+pub fn lox() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_crafting-interpreters-vm"))
+}
+
+/// A .loxb file holding code with the given line table and number constants, for chunks the
+/// compiler and assembler would never produce.
+pub fn loxb(code: &[u8], lines: &[(u32, u32)], constants: &[f64]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let name = b"script";
+    payload.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    payload.extend_from_slice(name);
+
+    payload.extend_from_slice(&(code.len() as u32).to_be_bytes());
+    payload.extend_from_slice(code);
+
+    payload.extend_from_slice(&(lines.len() as u32).to_be_bytes());
+    for (line, offset) in lines {
+        payload.extend_from_slice(&line.to_be_bytes());
+        payload.extend_from_slice(&offset.to_be_bytes());
+    }
+
+    payload.extend_from_slice(&(constants.len() as u32).to_be_bytes());
+    for constant in constants {
+        payload.push(0);
+        payload.extend_from_slice(&constant.to_be_bytes());
+    }
+
+    let mut bytes = b"LOXB".to_vec();
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&crc32(&payload).to_be_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// A path in the temp directory that no other test uses. Tests remove what they create there.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+}
+
+/// Writes contents to a file at `temp_path(name)`.
+pub fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
@@ -0,0 +1,266 @@
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Stdio},
+};
+
+use common::{lox, temp_file};
+use serde_json::{json, Value};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = lox()
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Could not start debug adapter");
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {
+            child,
+            stdin,
+            stdout,
+            seq: 1,
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.seq;
+        self.seq += 1;
+
+        let content = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(
+            self.stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.stdin.flush().unwrap();
+
+        let response = self.next_message();
+        assert_eq!(response["type"], "response", "{}", response);
+        assert_eq!(response["request_seq"], seq);
+        assert_eq!(response["command"], command);
+        response
+    }
+
+    fn expect_event(&mut self, event: &str) -> Value {
+        let message = self.next_message();
+        assert_eq!(message["type"], "event", "{}", message);
+        assert_eq!(message["event"], event, "{}", message);
+        message["body"].clone()
+    }
+
+    fn next_message(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(length) = header.strip_prefix("Content-Length:") {
+                content_length = length.trim().parse().unwrap();
+            }
+        }
+
+        let mut content = vec![0; content_length];
+        self.stdout.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+}
+
+fn write_program(name: &str, source: &str) -> String {
+    let path = temp_file(&format!("{}.lox", name), source.as_bytes());
+    path.to_string_lossy().to_string()
+}
+
+fn launch(client: &mut Client, program: &str, stop_on_entry: bool) {
+    let response = client.request("initialize", json!({ "adapterID": "lox" }));
+    assert_eq!(response["body"]["supportsConfigurationDoneRequest"], true);
+
+    let response = client.request(
+        "launch",
+        json!({ "program": program, "stopOnEntry": stop_on_entry }),
+    );
+    assert_eq!(response["success"], true);
+    client.expect_event("initialized");
+}
+
+#[test]
+fn breakpoints_stepping_and_inspection() {
+    let program = write_program("dap-session", "(1 + 2)\n* 3\n\n== 9\n");
+    let mut client = Client::start();
+    launch(&mut client, &program, true);
+
+    let response = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": program }, "breakpoints": [{ "line": 2 }, { "line": 3 }] }),
+    );
+    let breakpoints = &response["body"]["breakpoints"];
+    assert_eq!(breakpoints[0], json!({ "verified": true, "line": 2 }));
+    assert_eq!(breakpoints[1], json!({ "verified": false, "line": 3 }));
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.expect_event("stopped")["reason"], "entry");
+
+    let response = client.request("threads", json!({}));
+    assert_eq!(response["body"]["threads"][0]["id"], 1);
+
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(response["body"]["stackFrames"][0]["line"], 1);
+    assert_eq!(
+        response["body"]["stackFrames"][0]["source"]["path"],
+        program
+    );
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "breakpoint");
+
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(response["body"]["stackFrames"][0]["line"], 2);
+
+    let response = client.request("scopes", json!({ "frameId": 0 }));
+    let reference = response["body"]["scopes"][0]["variablesReference"].clone();
+    let response = client.request("variables", json!({ "variablesReference": reference }));
+    assert_eq!(
+        response["body"]["variables"],
+        json!([{ "name": "[0]", "value": "3", "type": "number", "variablesReference": 0 }])
+    );
+
+    let response = client.request("evaluate", json!({ "expression": "\"a\" + \"b\"" }));
+    assert_eq!(response["body"]["result"], "\"ab\"");
+    assert_eq!(response["body"]["type"], "string");
+
+    let response = client.request("evaluate", json!({ "expression": "1 + nil" }));
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["message"],
+        "[line 1] Error: Operands must be two numbers or two strings"
+    );
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "step");
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(response["body"]["stackFrames"][0]["line"], 4);
+
+    client.request(
+        "stepIn",
+        json!({ "threadId": 1, "granularity": "instruction" }),
+    );
+    assert_eq!(client.expect_event("stopped")["reason"], "step");
+    let response = client.request("variables", json!({ "variablesReference": reference }));
+    assert_eq!(response["body"]["variables"][1]["value"], "9");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("output")["output"], "true\n");
+    assert_eq!(client.expect_event("exited")["exitCode"], 0);
+    client.expect_event("terminated");
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(&program).unwrap();
+}
+
+#[test]
+fn runs_to_completion_without_breakpoints() {
+    let program = write_program("dap-run", "\"lox\" + \"!\"\n");
+    let mut client = Client::start();
+    launch(&mut client, &program, false);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.expect_event("output")["output"], "lox!\n");
+    client.expect_event("exited");
+    client.expect_event("terminated");
+
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(response["success"], false);
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(&program).unwrap();
+}
+
+// A runtime error ends the program with the exit code the CLI would give it.
+#[test]
+fn reports_runtime_errors_in_the_exit_code() {
+    let program = write_program("dap-runtime-error", "1 +\n  nil\n");
+    let mut client = Client::start();
+    launch(&mut client, &program, false);
+
+    client.request("configurationDone", json!({}));
+    let output = client.expect_event("output");
+    assert_eq!(output["category"], "stderr");
+    assert_eq!(
+        output["output"],
+        "[line 2] Error: Operands must be two numbers or two strings\n"
+    );
+    assert_eq!(client.expect_event("exited")["exitCode"], 70);
+    client.expect_event("terminated");
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(&program).unwrap();
+}
+
+#[test]
+fn launch_reports_compile_errors() {
+    let program = write_program("dap-error", "1 +\n");
+    let mut client = Client::start();
+    client.request("initialize", json!({ "adapterID": "lox" }));
+
+    let response = client.request("launch", json!({ "program": program }));
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["message"],
+        "[line 2] Error at end: Expect expression."
+    );
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(&program).unwrap();
+}
+
+// A body that isn't JSON is reported in the client's console, and the session carries on.
+#[test]
+fn keeps_reading_after_a_malformed_message() {
+    let mut client = Client::start();
+    write!(client.stdin, "Content-Length: 9\r\n\r\n{{not json").unwrap();
+    client.stdin.flush().unwrap();
+
+    let output = client.expect_event("output");
+    assert_eq!(output["category"], "console");
+    assert!(
+        output["output"]
+            .as_str()
+            .unwrap()
+            .starts_with("Ignoring a message that isn't JSON: "),
+        "{}",
+        output
+    );
+
+    let response = client.request("initialize", json!({ "adapterID": "lox" }));
+    assert_eq!(response["success"], true);
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+}
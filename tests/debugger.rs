@@ -0,0 +1,154 @@
+mod common;
+
+use std::{
+    io::Write,
+    process::{Output, Stdio},
+};
+
+use common::{lox, temp_file};
+
+const SOURCE: &str = "1 +\n  2 *\n  3";
+
+// Runs source under the debugger with commands typed one per line.
+fn debug_source(name: &str, source: &str, commands: &str) -> Output {
+    let path = temp_file(name, source.as_bytes());
+    let mut child = lox()
+        .arg("--debug")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run program");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("Could not run program");
+    std::fs::remove_file(&path).unwrap();
+
+    output
+}
+
+// Runs SOURCE under the debugger with commands typed one per line, and returns what it printed.
+fn debug(name: &str, commands: &str) -> String {
+    let output = debug_source(name, SOURCE, commands);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn sets_and_lists_breakpoints() {
+    assert_eq!(
+        debug(
+            "break.lox",
+            "break\nbreak 2\nbreak *4\nbreak\nbreak 9\nbreak *1\nbreak *1000\nbreak x\nq\n"
+        ),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb) No breakpoints.
+(loxdb) Breakpoint 0 set.
+(loxdb) Breakpoint 1 set.
+(loxdb) 0: line 2
+1: *0004
+(loxdb) No code on line 9.
+(loxdb) No instruction at offset 0001.
+(loxdb) No instruction at offset 1000.
+(loxdb) Expected a line, found 'x'.
+(loxdb) "
+    );
+}
+
+#[test]
+fn steps_a_line_at_a_time() {
+    assert_eq!(
+        debug("step.lox", "step\nstack\nstep\nstack\nq\n"),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb)    2 |   2 *
+0002    2 Constant            1 '2'
+(loxdb) [  0] 1 (number)
+(loxdb)    3 |   3
+0004    3 Constant            2 '3'
+(loxdb) [  1] 2 (number)
+[  0] 1 (number)
+(loxdb) "
+    );
+}
+
+#[test]
+fn continues_to_a_breakpoint_and_then_the_end() {
+    assert_eq!(
+        debug("continue.lox", "break *5\ncontinue\nstack\ncontinue\n"),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb) No instruction at offset 0005.
+(loxdb) 7
+Program finished.
+(loxdb) The stack is empty.
+(loxdb) The program is not running.
+(loxdb) 
+"
+    );
+}
+
+#[test]
+fn stops_at_a_breakpoint_on_continue() {
+    assert_eq!(
+        debug("breakpoint.lox", "break *6\ncontinue\nstack\nq\n"),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb) Breakpoint 0 set.
+(loxdb)    3 |   3
+0006    | Multiply
+(loxdb) [  2] 3 (number)
+[  1] 2 (number)
+[  0] 1 (number)
+(loxdb) "
+    );
+}
+
+// Nothing after quit is read, and the program is left unfinished.
+#[test]
+fn quits_without_running_the_rest() {
+    assert_eq!(
+        debug("quit.lox", "quit\ncontinue\n"),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb) "
+    );
+}
+
+// A runtime error ends the program, and the debugger exits the way running it would have.
+#[test]
+fn stops_on_a_runtime_error() {
+    let output = debug_source("runtime-error.lox", "1 +\n  nil", "continue\ncontinue\n");
+    assert_eq!(output.status.code(), Some(70), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 2] Error: Operands must be two numbers or two strings\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "\
+Type 'help' for a list of commands.
+   1 | 1 +
+0000    1 Constant            0 '1'
+(loxdb) Program stopped on a runtime error.
+(loxdb) The program is not running.
+(loxdb) \n"
+    );
+}
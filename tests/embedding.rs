@@ -0,0 +1,170 @@
+use crafting_interpreters_vm::{
+    Chunk, ExecutionState, InterpretError, Opcode, Optimizations, SharedBuffer, Value,
+    ValueTypeError, VerifyError, Vm,
+};
+
+#[test]
+fn evaluates_source_to_a_value() {
+    let mut vm = Vm::new();
+
+    let value = vm.evaluate("1 + 2 * 3").unwrap();
+    assert_eq!(f64::try_from(value), Ok(7.0));
+
+    let value = vm.evaluate("\"a\" + \"b\"").unwrap();
+    assert_eq!(String::try_from(value), Ok("ab".to_string()));
+
+    let value = vm.evaluate("1 < 2").unwrap();
+    assert_eq!(bool::try_from(value), Ok(true));
+}
+
+#[test]
+fn converts_between_values_and_rust_types() {
+    assert_eq!(Value::from(1.5), Value::number(1.5));
+    assert_eq!(Value::from(true), Value::bool(true));
+    assert!(Value::from(None::<f64>).is_nil());
+    assert_eq!(Value::from("lox").to_string(), "lox");
+    assert_eq!(Value::from(String::from("lox")).type_name(), "string");
+
+    assert_eq!(
+        f64::try_from(Value::from("1")),
+        Err(ValueTypeError {
+            expected: "number",
+            found: "string",
+        })
+    );
+    assert_eq!(
+        String::try_from(Value::nil()).unwrap_err().to_string(),
+        "Expected string but found nil"
+    );
+}
+
+#[test]
+fn builder_sets_output_and_error_sinks() {
+    let output = SharedBuffer::default();
+    let errors = SharedBuffer::default();
+    let mut vm = Vm::builder()
+        .output(Box::new(output.clone()))
+        .error_output(Box::new(errors.clone()))
+        .trace(false)
+        .build();
+
+    vm.interpret("\"hello\" + \" world\"").unwrap();
+    assert_eq!(output.take(), b"hello world\n");
+
+    let result = vm.interpret("-nil");
+    assert!(matches!(result, Err(InterpretError::RuntimeError(_))));
+    assert_eq!(errors.take(), b"[line 1] Error: Operand must be a number\n");
+}
+
+// The DUMP listing goes to the trace output along with the trace, not straight to stderr.
+#[test]
+fn builder_sends_the_listing_to_the_trace_output() {
+    let output = SharedBuffer::default();
+    let trace = SharedBuffer::default();
+    let mut vm = Vm::builder()
+        .output(Box::new(output.clone()))
+        .trace_output(Box::new(trace.clone()))
+        .trace(false)
+        .dump(true)
+        .build();
+
+    vm.interpret("1 + 2").unwrap();
+    assert_eq!(output.take(), b"3\n");
+    assert_eq!(
+        String::from_utf8(trace.take()).unwrap(),
+        "== main ==\n0000    1 Constant            0 '3'\n0002    | Return\n\n"
+    );
+}
+
+// A program that needs more stack than the VM has is rejected before it runs.
+#[test]
+fn builder_sets_stack_size() {
+    let source = "1 + (2 + (3 + 4))";
+
+    let mut vm = Vm::builder()
+        .stack_size(3)
+        .error_output(Box::new(std::io::sink()))
+        .optimizations(Optimizations::none())
+        .build();
+    let result = vm.evaluate(source);
+    assert!(matches!(
+        result,
+        Err(InterpretError::InvalidBytecode(
+            VerifyError::StackOverflow {
+                opcode: Opcode::Constant,
+                ..
+            }
+        ))
+    ));
+
+    let mut vm = Vm::builder()
+        .stack_size(4)
+        .optimizations(Optimizations::none())
+        .build();
+    assert_eq!(f64::try_from(vm.evaluate(source).unwrap()), Ok(10.0));
+}
+
+// A compiled chunk can be stepped through one instruction at a time.
+#[test]
+fn steps_through_a_compiled_chunk() {
+    let output = SharedBuffer::default();
+    let mut vm = Vm::builder().output(Box::new(output.clone())).build();
+
+    let chunk: Chunk = vm.compile("1 + 2").unwrap();
+    vm.load(chunk).unwrap();
+    while vm.step().unwrap() == ExecutionState::Running {}
+    assert_eq!(output.take(), b"3\n");
+
+    let result = vm.load(Chunk::new("empty".to_string()));
+    assert!(matches!(
+        result,
+        Err(InterpretError::InvalidBytecode(VerifyError::EmptyChunk))
+    ));
+}
+
+// Values are copied on every push, pop and constant load, so objects don't get to make them any
+// bigger than a string does.
+#[test]
+fn values_stay_small() {
+    let expected = match cfg!(feature = "nan-boxing") {
+        true => 8,
+        false => 24,
+    };
+    assert_eq!(std::mem::size_of::<Value>(), expected);
+}
+
+// Before a chunk is loaded, and once it has returned or failed, there is nothing for run or step
+// to do, rather than something for them to panic on.
+#[test]
+fn runs_nothing_without_a_chunk_or_after_the_end() {
+    for trace in [false, true] {
+        let output = SharedBuffer::default();
+        let mut vm = Vm::builder()
+            .output(Box::new(output.clone()))
+            .error_output(Box::new(std::io::sink()))
+            .trace(trace)
+            .trace_output(Box::new(std::io::sink()))
+            .build();
+        assert_eq!(vm.step().unwrap(), ExecutionState::Finished);
+        vm.run().unwrap();
+
+        // The code after the return would pop from an empty stack.
+        let mut chunk = Chunk::new("after-return".to_string());
+        chunk.write(Opcode::Nil, 1);
+        chunk.write(Opcode::Return, 1);
+        chunk.write(Opcode::Negate, 2);
+        chunk.write(Opcode::Return, 2);
+        vm.load(chunk).unwrap();
+        vm.run().unwrap();
+        assert_eq!(output.take(), b"nil\n");
+        assert_eq!(vm.step().unwrap(), ExecutionState::Finished);
+        vm.run().unwrap();
+        assert!(output.take().is_empty());
+
+        let chunk = vm.compile("-nil").unwrap();
+        vm.load(chunk).unwrap();
+        assert!(matches!(vm.run(), Err(InterpretError::RuntimeError(_))));
+        assert_eq!(vm.step().unwrap(), ExecutionState::Finished);
+        vm.run().unwrap();
+    }
+}
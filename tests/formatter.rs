@@ -0,0 +1,128 @@
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+use common::{lox, temp_file};
+
+fn run(args: &[&str]) -> Output {
+    lox().args(args).output().expect("Could not run program")
+}
+
+// Formats source in place and returns what was written back.
+fn format(name: &str, source: &str) -> String {
+    let path = temp_file(name, source.as_bytes());
+    let output = run(&["fmt", path.to_str().unwrap()]);
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    formatted
+}
+
+fn lox_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            lox_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn keeps_comments() {
+    assert_eq!(
+        format(
+            "comments.lox",
+            "// leading\n(1+2) // after group\n*   -3 // after three\n\n// at the end\n"
+        ),
+        "// leading\n(1 + 2) // after group\n    * -3 // after three\n// at the end\n"
+    );
+}
+
+// Only the operators with the lowest precedence are broken before, while each side fits.
+#[test]
+fn wraps_at_the_line_width() {
+    let left = "1000 + 1001 + 1002 + 1003 + 1004 + 1005 + 1006 + 1007";
+    let right = "2000 * 2001 * 2002 * 2003 * 2004 * 2005 * 2006 * 2007";
+
+    assert_eq!(
+        format("wrap.lox", &format!("{} == {}", left, right)),
+        format!("{}\n    == {}\n", left, right)
+    );
+    assert_eq!(format("no-wrap.lox", "1+2 ==3"), "1 + 2 == 3\n");
+}
+
+// Every test program that compiles formats to something that formats the same again, and still
+// runs the same.
+#[test]
+fn is_idempotent_across_the_test_programs() {
+    let mut files = Vec::new();
+    lox_files(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"),
+        &mut files,
+    );
+    files.sort();
+
+    let mut formatted = 0;
+    for file in files {
+        let source = std::fs::read_to_string(&file).unwrap();
+        let path = temp_file("idempotent.lox", source.as_bytes());
+        let path = path.to_str().unwrap();
+
+        if !run(&["fmt", path]).status.success() {
+            // NOTE: Programs that don't compile, such as the error tests, can't be formatted.
+            assert!(!run(&[file.to_str().unwrap()]).status.success());
+            std::fs::remove_file(path).unwrap();
+            continue;
+        }
+
+        let check = run(&["fmt", "--check", path]);
+        assert!(check.status.success(), "{}: {:?}", file.display(), check);
+        assert!(check.stdout.is_empty(), "{}: {:?}", file.display(), check);
+
+        let before = run(&[file.to_str().unwrap()]);
+        let after = run(&[path]);
+        assert_eq!(before.stdout, after.stdout, "{}", file.display());
+        assert_eq!(before.status, after.status, "{}", file.display());
+
+        std::fs::remove_file(path).unwrap();
+        formatted += 1;
+    }
+
+    assert!(formatted > 0);
+}
+
+#[test]
+fn check_exits_with_an_error_when_a_file_would_change() {
+    let path = temp_file("check.lox", b"1+2\n");
+    let output = run(&["fmt", "--check", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("Would reformat '{}'\n", path.display())
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "1+2\n");
+
+    std::fs::write(&path, "1 + 2\n").unwrap();
+    let output = run(&["fmt", "--check", path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+// With nothing to format, a check would pass without having looked at anything.
+#[test]
+fn needs_a_path() {
+    for args in [&["fmt"][..], &["fmt", "--check"]] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(64), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+    }
+}
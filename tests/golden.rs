@@ -0,0 +1,88 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file};
+
+fn test(args: &[&str]) -> Output {
+    lox()
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("test")
+        .args(args)
+        .output()
+        .expect("Could not run program")
+}
+
+// Runs every annotated script under tests/lox.
+#[test]
+fn golden_scripts() {
+    let output = test(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.ends_with(" passed, 0 failed\n"), "{}", stdout);
+}
+
+// A script that doesn't do what it says fails with a diff against what actually happened.
+#[test]
+fn reports_a_diff_on_mismatch() {
+    let path = temp_file("mismatch.lox", b"1 +\n\"a\" // expect: 3\n");
+
+    let output = test(&[path.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.starts_with("FAIL "), "{}", stdout);
+    assert!(stdout.contains("    - 3\n"), "{}", stdout);
+    assert!(
+        stdout.contains("    + [line 2] Error: Operands must be two numbers or two strings\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with("0 passed, 1 failed\n"), "{}", stdout);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn only_runs_scripts_matching_the_filter() {
+    let output = test(&["--filter", "arithmetic/nan"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert_eq!(stdout, "1 passed, 0 failed\n");
+}
+
+// The flags that say how to compile Lox source apply to the scripts that are run.
+#[test]
+fn runs_scripts_the_way_the_flags_say() {
+    let nested = format!("{}1{}", "1 + (".repeat(300), ")".repeat(300));
+    let file = temp_file(
+        "flags.lox",
+        format!("{} // expect: 301\n", nested).as_bytes(),
+    );
+    let path = file.to_str().unwrap();
+
+    let output = test(&[path]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Without folding, the script needs more stack than the VM has.
+    let output = test(&["--no-opt", path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(
+        stdout.contains("    + Invalid bytecode: ConstantLong would overflow the stack"),
+        "{}",
+        stdout
+    );
+
+    std::fs::write(
+        path,
+        "1 + nil // expect runtime error: Operands must be two numbers or two strings\n",
+    )
+    .unwrap();
+    let output = test(&[path]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = test(&["--typecheck", path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("    + [line 1] Error at '+'"), "{}", stdout);
+    std::fs::remove_file(&file).unwrap();
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{ChildStdout, ExitStatus, Stdio},
+};
+
+use common::lox;
+use serde_json::{json, Value};
+
+// Each file in tests/lsp is a recorded session: a list of steps that either "send" a message to
+// the server, "send_raw" a body as it is, or "expect" the next message it writes back.
+fn replay(session: &Path) {
+    let steps: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(session).unwrap()).unwrap();
+
+    let status = run_session(&session.display().to_string(), &steps);
+    assert!(status.success(), "{}", session.display());
+}
+
+fn run_session(name: &str, steps: &[Value]) -> ExitStatus {
+    let mut child = lox()
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Could not start language server");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    for (n, step) in steps.iter().enumerate() {
+        let content = match (step.get("send"), step.get("send_raw")) {
+            (Some(message), _) => Some(message.to_string()),
+            (_, Some(body)) => Some(body.as_str().unwrap().to_string()),
+            _ => None,
+        };
+
+        if let Some(content) = content {
+            write!(
+                stdin,
+                "Content-Length: {}\r\n\r\n{}",
+                content.len(),
+                content
+            )
+            .unwrap();
+            stdin.flush().unwrap();
+        } else if let Some(expected) = step.get("expect") {
+            let actual = next_message(&mut stdout);
+            assert_eq!(&actual, expected, "{} step {}", name, n);
+        }
+    }
+
+    drop(stdin);
+    child.wait().unwrap()
+}
+
+fn next_message(stdout: &mut BufReader<ChildStdout>) -> Value {
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        stdout.read_line(&mut header).unwrap();
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().unwrap();
+        }
+    }
+
+    let mut content = vec![0; content_length];
+    stdout.read_exact(&mut content).unwrap();
+    serde_json::from_slice(&content).unwrap()
+}
+
+#[test]
+fn recorded_sessions() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lsp");
+    let mut sessions: Vec<_> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "json"))
+        .collect();
+    sessions.sort();
+
+    assert!(!sessions.is_empty());
+    for session in sessions {
+        replay(&session);
+    }
+}
+
+// The protocol has the server exit with an error when it was never asked to shut down.
+#[test]
+fn exit_without_shutdown_is_an_error() {
+    let initialize = json!({
+        "send": {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}
+    });
+    let exit = json!({ "send": {"jsonrpc": "2.0", "method": "exit"} });
+
+    let status = run_session("exit", &[initialize.clone(), exit.clone()]);
+    assert_eq!(status.code(), Some(1));
+
+    let shutdown = json!({ "send": {"jsonrpc": "2.0", "id": 2, "method": "shutdown"} });
+    let status = run_session("shutdown and exit", &[initialize, shutdown, exit]);
+    assert_eq!(status.code(), Some(0));
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file};
+
+fn run(source: &str, name: &str, flags: &[&str]) -> Output {
+    let path = temp_file(&format!("{}.lox", name), source.as_bytes());
+
+    let output = lox()
+        .arg(&path)
+        .args(flags)
+        .env("DUMP", "1")
+        .output()
+        .expect("Could not run program");
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+fn runtime_errors(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter(|line| line.starts_with("[line"))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn assert_same_behaviour(program: &str, optimized: &Output, unoptimized: &Output) {
+    assert_eq!(optimized.stdout, unoptimized.stdout, "{:?}", program);
+    assert_eq!(
+        optimized.status.success(),
+        unoptimized.status.success(),
+        "{:?}",
+        program
+    );
+    assert_eq!(
+        runtime_errors(optimized),
+        runtime_errors(unoptimized),
+        "{:?}",
+        program
+    );
+}
+
+// Folded constants must give exactly what the VM would have computed, and operations that fail
+// at runtime must still fail, on the same line.
+#[test]
+fn folding_matches_runtime_semantics() {
+    let programs = [
+        "1 + 2 * 3 - 4 / 5",
+        "-(1 + 2) * -3",
+        "1 / 0",
+        "-(0 / 0)",
+        "(0 / 0) == (0 / 0)",
+        "(0 / 0) >= 1",
+        "(0 / 0) <= 1",
+        "(0 / 0) != 1",
+        "\"a\" + \"b\"",
+        "\"a\" == \"a\"",
+        "nil == nil",
+        "nil != false",
+        "!nil == !0",
+        "!\"\"",
+        "1 < 2 == 2 > 1",
+        "-\"a\"",
+        "1 +\n\nnil",
+        "\"a\" + 1",
+        "true < false",
+        "(1 + 2) * (3 - nil)",
+    ];
+
+    for (n, program) in programs.iter().enumerate() {
+        let name = format!("fold-{}", n);
+        let optimized = run(program, &name, &[]);
+        let unoptimized = run(program, &name, &["--no-opt"]);
+        assert_same_behaviour(program, &optimized, &unoptimized);
+    }
+}
+
+// Without folding, comparisons reach the bytecode and the peephole pass fuses them.
+#[test]
+fn peephole_matches_unoptimized_bytecode() {
+    let programs = [
+        ("1 != 2", "NotEqual"),
+        ("nil != nil", "NotEqual"),
+        ("(0 / 0) >= 1", "GreaterEqual"),
+        ("2 >= 1", "GreaterEqual"),
+        ("(0 / 0) <= 1", "LessEqual"),
+        ("1 <=\n\n2", "LessEqual"),
+        ("\"a\" <=\n\"b\"", "LessEqual"),
+        ("true >= nil", "GreaterEqual"),
+        ("!(1 != 2) != !(3 >= 4)", "NotEqual"),
+    ];
+
+    for (n, (program, opcode)) in programs.iter().enumerate() {
+        let name = format!("peephole-{}", n);
+        let optimized = run(program, &name, &["--no-fold"]);
+        let unoptimized = run(program, &name, &["--no-opt"]);
+
+        let dump = String::from_utf8_lossy(&optimized.stderr);
+        assert!(dump.contains(opcode), "{:?}\n{}", program, dump);
+        assert_same_behaviour(program, &optimized, &unoptimized);
+    }
+}
+
+// Without folding, additions of a literal and a bare `nil` script compile to superinstructions,
+// from the code generator or, behind a grouping, from the peephole pass.
+#[test]
+fn superinstructions_match_unoptimized_bytecode() {
+    let programs = [
+        ("nil", "ReturnNil"),
+        ("(nil)", "ReturnNil"),
+        ("(\nnil\n)", "ReturnNil"),
+        ("1 + (2)", "AddConstant"),
+        ("nil +\n(\n2)", "AddConstant"),
+        ("1 + 2", "AddConstant"),
+        ("\"a\" + \"b\"", "AddConstant"),
+        ("(1 + 2) + 3 + 4", "AddConstant"),
+        ("(1 + nil) + 2", "AddConstant"),
+        ("nil +\n\n2", "AddConstant"),
+        ("true + \"a\"", "AddConstant"),
+        ("-1 + (2 + 3)", "AddConstant"),
+    ];
+
+    for (n, (program, opcode)) in programs.iter().enumerate() {
+        let name = format!("superinstruction-{}", n);
+        let optimized = run(program, &name, &["--no-fold"]);
+        let unoptimized = run(program, &name, &["--no-opt"]);
+
+        let dump = String::from_utf8_lossy(&optimized.stderr);
+        assert!(dump.contains(opcode), "{:?}\n{}", program, dump);
+        assert_same_behaviour(program, &optimized, &unoptimized);
+    }
+}
+
+// `--no-superinstructions` keeps the peephole pass from fusing into them too.
+#[test]
+fn no_superinstructions_leaves_the_pairs_alone() {
+    for (n, program) in ["(nil)", "1 + (2)"].iter().enumerate() {
+        let name = format!("no-superinstruction-{}", n);
+        let output = run(program, &name, &["--no-fold", "--no-superinstructions"]);
+
+        let dump = String::from_utf8_lossy(&output.stderr);
+        assert!(!dump.contains("AddConstant"), "{:?}\n{}", program, dump);
+        assert!(!dump.contains("ReturnNil"), "{:?}\n{}", program, dump);
+    }
+}
+
+// Every golden script behaves the same with each optimization on or off.
+#[test]
+fn golden_scripts_pass_with_and_without_optimizations() {
+    for flags in [
+        &[][..],
+        &["--no-opt"],
+        &["--no-fold"],
+        &["--no-superinstructions"],
+    ] {
+        let output = lox()
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("test")
+            .args(flags)
+            .output()
+            .expect("Could not run program");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{:?}: {}", flags, stdout);
+        assert!(
+            stdout.ends_with(" passed, 0 failed\n"),
+            "{:?}: {}",
+            flags,
+            stdout
+        );
+    }
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+use common::{lox, temp_path};
+
+fn repl(home: &Path, input: &str) -> Output {
+    repl_with_args(home, &[], input)
+}
+
+fn repl_with_args(home: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = lox()
+        .args(args)
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run program");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().expect("Could not run program")
+}
+
+// Runs the REPL on a terminal, by way of util-linux's script, as it only colors and completes
+// input there. The terminal echoes each line back before the REPL draws it.
+#[cfg(target_os = "linux")]
+fn terminal(home: &Path, lines: &[&str]) -> String {
+    use std::io::Read;
+
+    let mut child = Command::new("script")
+        .args([
+            "-qec",
+            env!("CARGO_BIN_EXE_crafting-interpreters-vm"),
+            "/dev/null",
+        ])
+        .env("HOME", home)
+        .env("TERM", "xterm")
+        .env_remove("NO_COLOR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Could not run script");
+
+    // NOTE: Input typed ahead of a prompt can be thrown away when the REPL sets up the terminal,
+    //       so each line waits for the prompt to turn on bracketed paste first.
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut output = Vec::new();
+    for (prompts, line) in lines.iter().enumerate() {
+        while count(&output, b"\x1b[?2004h") <= prompts {
+            let mut buffer = [0; 256];
+            let read = stdout.read(&mut buffer).unwrap();
+            assert!(read > 0, "{:?}", String::from_utf8_lossy(&output));
+            output.extend_from_slice(&buffer[..read]);
+        }
+        stdin.write_all(line.as_bytes()).unwrap();
+    }
+
+    drop(stdin);
+    stdout.read_to_end(&mut output).unwrap();
+    child.wait().unwrap();
+    String::from_utf8_lossy(&output).to_string()
+}
+
+#[cfg(target_os = "linux")]
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|window| *window == needle)
+        .count()
+}
+
+// An empty home directory, for the history file. Each test removes it when done.
+fn home(name: &str) -> PathBuf {
+    let home = temp_path(name);
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    home
+}
+
+// Errors are reported and the session carries on until the end of input.
+#[test]
+fn continues_after_errors() {
+    let home = home("repl-errors");
+    let output = repl(&home, "1 +\n-nil\n1 + 2\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"3\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at end: Expect expression.\n[line 1] Error: Operand must be a number\n"
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn waits_for_unbalanced_parentheses() {
+    let home = home("repl-multiline");
+    let output = repl(&home, "(1 +\n(2\n* 3))\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn runs_meta_commands() {
+    let home = home("repl-commands");
+    let script = home.join("script.lox");
+    std::fs::write(&script, "\"from\" + \" file\"").unwrap();
+
+    let input = format!(
+        ":dis\n-(2)\n:dis\n:load {}\n:reset\n:dis\n:nope\n",
+        script.display()
+    );
+    let output = repl(&home, &input);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout,
+        "Nothing has been compiled yet\n\
+         -2\n\
+         == main ==\n\
+         0000    1 Constant            0 '-2'\n\
+         0002    | Return\n\
+         from file\n\
+         Nothing has been compiled yet\n\
+         Unknown command ':nope', type :help for a list\n"
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+// Bytecode the verifier rejects is reported, as the VM has no error output of its own for it.
+#[test]
+fn reports_invalid_bytecode() {
+    let home = home("repl-invalid");
+    let source = format!("{}1{}\n", "1 + (".repeat(300), ")".repeat(300));
+    let output = repl_with_args(&home, &["--no-opt"], &source);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Invalid bytecode: ConstantLong would overflow the stack at offset 0512\n"
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn loads_files_with_the_repls_settings() {
+    let home = home("repl-load-typecheck");
+    let script = home.join("script.lox");
+    std::fs::write(&script, "1 + nil\n").unwrap();
+
+    let input = format!(":load {}\n", script.display());
+    let output = repl_with_args(&home, &["--typecheck"], &input);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at '+': Operands must be two numbers or two strings, but found num and \
+         nil.\n"
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn keeps_history_between_sessions() {
+    let home = home("repl-history");
+    repl(&home, "1 + 2\n(1\n)\n");
+
+    let history = std::fs::read_to_string(home.join(".lox_history")).unwrap();
+    assert!(history.ends_with("1 + 2\n(1\\n)\n"), "{}", history);
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+// Highlighting and completion are tested in src/repl.rs. This checks that the REPL uses them
+// on a terminal.
+#[cfg(target_os = "linux")]
+#[test]
+fn colors_and_completes_on_a_terminal() {
+    let home = home("repl-terminal");
+    let output = terminal(&home, &["!tr\t\n"]);
+    assert!(
+        output.contains("> \x1b[36m!\x1b[0m\x1b[35mtrue\x1b[0m\r"),
+        "{:?}",
+        output
+    );
+    assert!(output.contains("\r\nfalse\r\n"), "{:?}", output);
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use std::{path::Path, process::Output};
+
+use common::{lox, loxb, temp_file, temp_path};
+use crafting_interpreters_vm::serializer::crc32;
+
+const SOURCE: &str = "(\"a\" + \"b\" == nil) != !(nil == false)\n  == (1.5 * -2 < 3)";
+
+fn run(args: &[&str], env: &[(&str, &str)]) -> Output {
+    lox()
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .expect("Could not run program")
+}
+
+fn compile(name: &str) -> (std::path::PathBuf, Vec<u8>) {
+    let source = temp_file(&format!("{}.lox", name), SOURCE.as_bytes());
+    let compiled = source.with_extension("loxb");
+    let output = run(
+        &[
+            "compile",
+            "--no-fold",
+            source.to_str().unwrap(),
+            "-o",
+            compiled.to_str().unwrap(),
+        ],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    std::fs::remove_file(&source).unwrap();
+
+    let bytes = std::fs::read(&compiled).unwrap();
+    std::fs::remove_file(&compiled).unwrap();
+    (compiled, bytes)
+}
+
+// Loads a .loxb file, which has to fail before anything runs, and returns the reason.
+fn load_error(path: &Path, bytes: &[u8]) -> String {
+    std::fs::write(path, bytes).unwrap();
+    let output = run(&[path.to_str().unwrap()], &[]);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let prefix = format!("Could not load '{}': ", path.display());
+    stderr
+        .strip_prefix(&prefix)
+        .unwrap_or_else(|| panic!("Unexpected error: {}", stderr))
+        .trim_end()
+        .to_string()
+}
+
+// Updates the checksum after the payload has been changed.
+fn reseal(bytes: &mut [u8]) {
+    let checksum = crc32(&bytes[14..]);
+    bytes[6..10].copy_from_slice(&checksum.to_be_bytes());
+}
+
+// The compiled file runs the same as its source, instruction for instruction and line for line.
+#[test]
+fn round_trips_a_compiled_chunk() {
+    let (path, bytes) = compile("round-trip");
+    let source = temp_file("round-trip-source.lox", SOURCE.as_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let from_source = run(&["--no-fold", source.to_str().unwrap()], &[("DEBUG", "1")]);
+    let from_bytecode = run(&[path.to_str().unwrap()], &[("DEBUG", "1")]);
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(from_source.status.success(), "{:?}", from_source);
+    assert!(from_bytecode.status.success(), "{:?}", from_bytecode);
+    assert_eq!(from_bytecode.stdout, b"true\n");
+    assert_eq!(from_bytecode.stdout, from_source.stdout);
+    assert_eq!(from_bytecode.stderr, from_source.stderr);
+}
+
+#[test]
+fn rejects_a_bad_magic_number() {
+    let (path, mut bytes) = compile("magic");
+    bytes[0..4].copy_from_slice(b"LOXA");
+    assert_eq!(load_error(&path, &bytes), "Not a Lox bytecode file");
+}
+
+#[test]
+fn rejects_an_unsupported_version() {
+    let (path, mut bytes) = compile("version");
+    bytes[4..6].copy_from_slice(&2u16.to_be_bytes());
+    assert_eq!(
+        load_error(&path, &bytes),
+        "Unsupported bytecode version 2 (expected 1)"
+    );
+}
+
+#[test]
+fn rejects_a_checksum_mismatch() {
+    let (path, mut bytes) = compile("checksum");
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    assert_eq!(
+        load_error(&path, &bytes),
+        "Bytecode checksum does not match"
+    );
+}
+
+#[test]
+fn rejects_truncated_input() {
+    let (path, bytes) = compile("truncated");
+    assert_eq!(
+        load_error(&path, &bytes[..bytes.len() - 1]),
+        format!("Unexpected end of bytecode at byte {}", bytes.len() - 1)
+    );
+    assert_eq!(
+        load_error(&path, &bytes[..10]),
+        "Unexpected end of bytecode at byte 10"
+    );
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    let (path, mut bytes) = compile("trailing");
+    let length = bytes.len();
+    bytes.push(0);
+    assert_eq!(
+        load_error(&path, &bytes),
+        format!("Unexpected trailing bytes at byte {}", length)
+    );
+}
+
+// The payload's own lengths have to agree with the length in the header.
+#[test]
+fn rejects_trailing_bytes_inside_the_payload() {
+    let mut bytes = loxb(&[8, 0], &[(1, 0)], &[]);
+    let length = bytes.len();
+    bytes.push(0);
+    let payload_length = (bytes.len() - 14) as u32;
+    bytes[10..14].copy_from_slice(&payload_length.to_be_bytes());
+    reseal(&mut bytes);
+
+    let path = temp_path("inner.loxb");
+    assert_eq!(
+        load_error(&path, &bytes),
+        format!("Unexpected trailing bytes at byte {}", length)
+    );
+}
+
+// A line table that is missing or starts past offset 0 loads, and is left to the verifier.
+#[test]
+fn leaves_the_start_of_the_line_table_to_the_verifier() {
+    for (name, lines) in [("no-lines", &[][..]), ("late-lines", &[(1, 1)][..])] {
+        let path = temp_file(&format!("{}.loxb", name), &loxb(&[8, 0], lines, &[]));
+        let output = run(&[path.to_str().unwrap()], &[("DEBUG", "1")]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output.status.code(), Some(65));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!(
+                "Could not run '{}': Invalid bytecode: Line table has no line for offset 0000\n",
+                path.display()
+            )
+        );
+    }
+}
+
+#[test]
+fn rejects_a_line_table_out_of_order() {
+    let path = temp_path("order.loxb");
+    let bytes = loxb(&[8, 8, 0], &[(1, 0), (2, 2), (3, 1)], &[]);
+    assert!(load_error(&path, &bytes).starts_with("Invalid line table offset at byte "));
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::process::Output;
+
+use common::{lox, temp_file, temp_path};
+use crafting_interpreters_vm::{InterpretError, SharedBuffer, Vm};
+
+fn run(args: &[&str]) -> Output {
+    lox().args(args).output().expect("Could not run program")
+}
+
+// Returns what the checker reports for source, or None if it is fine.
+fn check(source: &str) -> Option<String> {
+    let errors = SharedBuffer::default();
+    let mut vm = Vm::builder()
+        .typecheck(true)
+        .output(Box::new(std::io::sink()))
+        .error_output(Box::new(errors.clone()))
+        .build();
+
+    match vm.compile(source) {
+        Ok(_) => None,
+        Err(_) => Some(String::from_utf8(errors.take()).unwrap()),
+    }
+}
+
+#[test]
+fn accepts_well_typed_source() {
+    let programs = [
+        "1 + 2 * -3",
+        "\"a\" + \"b\"",
+        "(1 < 2) == !nil",
+        "\"a\" == 1",
+        "!\"a\"",
+        "-(1 + 2) >= 3 / 4",
+    ];
+
+    for program in programs {
+        assert_eq!(check(program), None, "{:?}", program);
+    }
+}
+
+#[test]
+fn reports_mismatches_as_compile_errors() {
+    let programs = [
+        (
+            "1 + \"a\"",
+            "[line 1] Error at '+': Operands must be two numbers or two strings, but found num and str.\n",
+        ),
+        (
+            "-nil",
+            "[line 1] Error at '-': Operand must be a number, but found nil.\n",
+        ),
+        (
+            "1 <\n\"a\"",
+            "[line 1] Error at '<': Operands must be numbers, but found num and str.\n",
+        ),
+        (
+            "true * 2 - 1",
+            "[line 1] Error at '*': Operands must be numbers, but found bool and num.\n",
+        ),
+    ];
+
+    for (program, expected) in programs {
+        assert_eq!(check(program).as_deref(), Some(expected), "{:?}", program);
+    }
+}
+
+// A mismatch is only reported once, not again by every operation around it.
+#[test]
+fn reports_each_mistake_once() {
+    assert_eq!(
+        check("-(-nil + 1) * 2").as_deref(),
+        Some("[line 1] Error at '-': Operand must be a number, but found nil.\n")
+    );
+}
+
+#[test]
+fn only_checks_when_asked() {
+    let output = run(&["-e", "1 + nil"]);
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run(&["--typecheck", "-e", "1 + nil"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+
+    let mut vm = Vm::builder()
+        .error_output(Box::new(std::io::sink()))
+        .build();
+    assert!(vm.compile("1 + nil").is_ok());
+    assert!(matches!(
+        vm.interpret("1 + nil"),
+        Err(InterpretError::RuntimeError(_))
+    ));
+}
+
+#[test]
+fn compile_checks_types_when_asked() {
+    let source = temp_file("typecheck-compile.lox", b"1 + nil\n");
+    let path = source.to_str().unwrap();
+    let output_path = temp_path("typecheck-compile.loxb");
+    let output_path = output_path.to_str().unwrap();
+
+    let output = run(&["--typecheck", "compile", path, "-o", output_path]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at '+': Operands must be two numbers or two strings, but found num and \
+         nil.\n"
+    );
+
+    // The single-pass compiler has no AST to check.
+    let output = run(&[
+        "--typecheck",
+        "compile",
+        "--single-pass",
+        path,
+        "-o",
+        output_path,
+    ]);
+    assert_eq!(output.status.code(), Some(64));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: --typecheck checks the AST, which --single-pass never builds\n"
+    );
+    std::fs::remove_file(&source).unwrap();
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{lox, loxb, temp_file};
+
+// Opcode bytes, as numbered in src/opcode.rs.
+const RETURN: u8 = 0;
+const CONSTANT: u8 = 1;
+const CONSTANT_LONG: u8 = 2;
+const ADD: u8 = 4;
+const NIL: u8 = 8;
+
+// Runs a .loxb file, which has to be rejected before any of it runs, and returns the reason.
+fn rejection(name: &str, bytes: &[u8]) -> String {
+    let path = temp_file(name, bytes);
+    let output = lox().arg(&path).output().expect("Could not run program");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let prefix = format!("Could not run '{}': Invalid bytecode: ", path.display());
+    stderr
+        .strip_prefix(&prefix)
+        .unwrap_or_else(|| panic!("Unexpected error: {}", stderr))
+        .trim_end()
+        .to_string()
+}
+
+#[test]
+fn accepts_a_valid_chunk() {
+    let path = temp_file(
+        "valid.loxb",
+        &loxb(&[CONSTANT, 0, RETURN], &[(1, 0)], &[1.5]),
+    );
+    let output = lox().arg(&path).output().expect("Could not run program");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"1.5\n");
+}
+
+#[test]
+fn rejects_an_empty_chunk() {
+    assert_eq!(
+        rejection("empty.loxb", &loxb(&[], &[], &[])),
+        "Chunk has no code"
+    );
+}
+
+#[test]
+fn rejects_a_chunk_without_a_line_table() {
+    assert_eq!(
+        rejection("no-lines.loxb", &loxb(&[NIL, RETURN], &[], &[])),
+        "Line table has no line for offset 0000"
+    );
+}
+
+#[test]
+fn rejects_a_line_table_that_starts_past_offset_0() {
+    assert_eq!(
+        rejection("late-lines.loxb", &loxb(&[NIL, RETURN], &[(1, 1)], &[])),
+        "Line table has no line for offset 0000"
+    );
+}
+
+#[test]
+fn rejects_an_unknown_opcode() {
+    assert_eq!(
+        rejection("unknown.loxb", &loxb(&[NIL, 200, RETURN], &[(1, 0)], &[])),
+        "Unknown opcode 200 at offset 0001"
+    );
+}
+
+#[test]
+fn rejects_a_truncated_operand() {
+    assert_eq!(
+        rejection(
+            "truncated.loxb",
+            &loxb(&[CONSTANT_LONG, 0, 0], &[(1, 0)], &[1.0])
+        ),
+        "Operand of ConstantLong runs past end of code at offset 0000"
+    );
+}
+
+#[test]
+fn rejects_a_constant_out_of_range() {
+    assert_eq!(
+        rejection(
+            "constant.loxb",
+            &loxb(&[CONSTANT, 1, RETURN], &[(1, 0)], &[1.0])
+        ),
+        "Constant 1 is out of range at offset 0000"
+    );
+}
+
+#[test]
+fn rejects_a_stack_underflow() {
+    assert_eq!(
+        rejection(
+            "underflow.loxb",
+            &loxb(&[CONSTANT, 0, ADD, RETURN], &[(1, 0)], &[1.0])
+        ),
+        "Add would underflow the stack at offset 0002"
+    );
+}
+
+#[test]
+fn rejects_a_stack_overflow() {
+    // NOTE: The CLI gives the VM a stack of 256 values.
+    let mut code = vec![NIL; 257];
+    code.push(RETURN);
+    assert_eq!(
+        rejection("overflow.loxb", &loxb(&code, &[(1, 0)], &[])),
+        "Nil would overflow the stack at offset 0256"
+    );
+}
+
+#[test]
+fn rejects_code_without_a_return() {
+    assert_eq!(
+        rejection("no-return.loxb", &loxb(&[NIL], &[(1, 0)], &[])),
+        "Code runs off the end without a return"
+    );
+}